@@ -13,6 +13,8 @@ pub enum ProveErrors {
     ProveResponseError(String),
     #[error("Missing program input")]
     MissingProgramInput,
+    #[error("Missing prove arguments")]
+    MissingProveArguments,
     #[error(transparent)]
     Parse(#[from] serde_json::Error),
     #[error(transparent)]
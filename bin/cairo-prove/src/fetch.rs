@@ -1,29 +1,35 @@
 use std::time::Duration;
 
-use prover_sdk::{sdk::ProverSDK, JobResponse, ProverResult};
+use futures::StreamExt;
+use prover_sdk::{sdk::ProverSDK, JobEvent, JobId, JobResponse, ProverResult};
 use serde_json::Value;
 use tokio::time::sleep;
 use tracing::info;
 
 use crate::errors::ProveErrors;
 
-pub async fn fetch_job_sse(sdk: ProverSDK, job: u64) -> Result<ProverResult, ProveErrors> {
+pub async fn fetch_job_sse(sdk: ProverSDK, job: JobId) -> Result<ProverResult, ProveErrors> {
     info!("Job ID: {}", job);
-    sdk.sse(job).await?;
-    info!("Job completed");
-    let response = sdk.get_job(job).await?;
-    let response = response.text().await?;
-    let json_response: JobResponse = serde_json::from_str(&response).unwrap();
-    if let JobResponse::Completed { result, .. } = json_response {
-        return Ok(result);
+    let events = sdk.job_events(&job, None).await?;
+    let mut events = std::pin::pin!(events);
+    while let Some(message) = events.next().await {
+        match message?.event {
+            JobEvent::Completed { result } => {
+                info!("Job completed");
+                return Ok(result);
+            }
+            JobEvent::Failed { error, .. } => return Err(ProveErrors::Custom(error)),
+            JobEvent::Cancelled => return Err(ProveErrors::Custom("Job cancelled".to_string())),
+            event => info!("Job {}", event.name()),
+        }
     }
     Err(ProveErrors::Custom("Job failed".to_string()))
 }
-pub async fn fetch_job_polling(sdk: ProverSDK, job: u64) -> Result<ProverResult, ProveErrors> {
+pub async fn fetch_job_polling(sdk: ProverSDK, job: JobId) -> Result<ProverResult, ProveErrors> {
     info!("Fetching job: {}", job);
     let mut counter = 0;
     loop {
-        let response = sdk.get_job(job).await?;
+        let response = sdk.get_job(&job).await?;
         let response = response.text().await?;
         let json_response: Value = serde_json::from_str(&response)?;
         if let Some(status) = json_response.get("status").and_then(Value::as_str) {
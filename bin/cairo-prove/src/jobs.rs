@@ -0,0 +1,16 @@
+use prover_sdk::{sdk::ProverSDK, ListJobsQuery};
+
+use crate::{errors::ProveErrors, JobsArgs};
+
+pub async fn list_jobs(args: JobsArgs, sdk: ProverSDK) -> Result<(), ProveErrors> {
+    let query = ListJobsQuery {
+        status: args.status,
+        created_after: args.created_after,
+        created_before: args.created_before,
+        cursor: args.cursor,
+        limit: args.limit,
+    };
+    let response = sdk.list_jobs(&query).await?;
+    println!("{}", serde_json::to_string_pretty(&response)?);
+    Ok(())
+}
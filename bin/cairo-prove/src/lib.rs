@@ -1,5 +1,6 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use errors::ProveErrors;
+use prover_sdk::{JobId, JobPriority, JobStatus};
 use serde::{Deserialize, Serialize};
 use starknet_types_core::felt::Felt;
 use std::{path::PathBuf, str::FromStr};
@@ -7,6 +8,7 @@ use url::Url;
 
 pub mod errors;
 pub mod fetch;
+pub mod jobs;
 pub mod prove;
 
 #[derive(Debug, Serialize, Deserialize, ValueEnum, Clone)]
@@ -29,9 +31,43 @@ impl FromStr for CairoVersion {
 
 #[derive(Parser, Debug, Clone)]
 #[clap(author, version, about, long_about = None)]
-pub struct Args {
+#[command(subcommand_negates_reqs = true)]
+pub struct Cli {
     #[arg(long, env)]
     pub prover_url: Url,
+    #[arg(long, env)]
+    pub prover_access_key: String,
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+    /// Proving a program is the default when no subcommand is given
+    #[command(flatten)]
+    pub args: Option<Args>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Commands {
+    /// List the jobs submitted with the access key
+    Jobs(JobsArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct JobsArgs {
+    #[arg(long)]
+    pub status: Option<JobStatus>,
+    /// Unix timestamp in seconds
+    #[arg(long)]
+    pub created_after: Option<u64>,
+    /// Unix timestamp in seconds
+    #[arg(long)]
+    pub created_before: Option<u64>,
+    #[arg(long)]
+    pub cursor: Option<JobId>,
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Args {
     #[arg(long, short, env, default_value = "v1")]
     pub cairo_version: CairoVersion,
     #[arg(long, short, env)]
@@ -49,8 +85,6 @@ pub struct Args {
     pub program_input: Vec<Felt>,
     #[arg(long, env)]
     pub program_output: PathBuf,
-    #[arg(long, env)]
-    pub prover_access_key: String,
     #[arg(long, env, default_value = "false")]
     pub wait: bool,
     #[arg(long, env, default_value = "false")]
@@ -59,6 +93,18 @@ pub struct Args {
     pub n_queries: Option<u32>,
     #[arg(long, env)]
     pub pow_bits: Option<u32>,
+    /// low, normal or high, capped at the priority the prover allows for the key
+    #[arg(long, env)]
+    pub priority: Option<JobPriority>,
+    /// JSON file with Stone parameters, e.g. `{"preset": "ethereum-verifier-friendly"}`
+    #[arg(long, env)]
+    pub prover_params: Option<PathBuf>,
+    /// JSON file overriding Stone prover config entries, e.g. `{"n_out_of_memory_merkle_layers": 0}`
+    #[arg(long, env)]
+    pub prover_config: Option<PathBuf>,
+    /// Only execute the program and write its output, step count and builtin usage
+    #[arg(long, env, default_value = "false")]
+    pub dry_run: bool,
 }
 
 fn validate_input(input: &str) -> Result<Vec<Felt>, ProveErrors> {
@@ -114,6 +160,47 @@ mod test {
         Ok(())
     }
     #[test]
+    fn test_parse_jobs_subcommand() {
+        let cli = Cli::try_parse_from([
+            "cairo-prove",
+            "--prover-url",
+            "http://localhost:3000",
+            "--prover-access-key",
+            "0x01",
+            "jobs",
+            "--status",
+            "completed",
+            "--limit",
+            "10",
+        ])
+        .unwrap();
+        assert!(cli.args.is_none());
+        let Some(Commands::Jobs(jobs)) = cli.command else {
+            panic!("expected the jobs subcommand");
+        };
+        assert_eq!(jobs.status, Some(JobStatus::Completed));
+        assert_eq!(jobs.limit, Some(10));
+    }
+    #[test]
+    fn test_parse_prove_args() {
+        let cli = Cli::try_parse_from([
+            "cairo-prove",
+            "--prover-url",
+            "http://localhost:3000",
+            "--prover-access-key",
+            "0x01",
+            "--layout",
+            "recursive",
+            "--program-path",
+            "program.json",
+            "--program-output",
+            "proof.json",
+        ])
+        .unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.args.unwrap().layout, "recursive");
+    }
+    #[test]
     fn test_validate_input_non_numeric() -> Result<(), ProveErrors> {
         let input = "[1,2,a,4,5]";
         let result = validate_input(input);
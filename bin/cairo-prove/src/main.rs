@@ -1,8 +1,9 @@
 use cairo_prove::errors::ProveErrors;
-use cairo_prove::prove::prove;
+use cairo_prove::jobs::list_jobs;
+use cairo_prove::prove::{prove, run};
 use cairo_prove::{
     fetch::{fetch_job_polling, fetch_job_sse},
-    Args,
+    Cli, Commands,
 };
 use clap::Parser;
 use prover_sdk::access_key::ProverAccessKey;
@@ -10,9 +11,19 @@ use prover_sdk::sdk::ProverSDK;
 #[tokio::main]
 pub async fn main() -> Result<(), ProveErrors> {
     tracing_subscriber::fmt().init();
-    let args = Args::parse();
-    let access_key = ProverAccessKey::from_hex_string(&args.prover_access_key.clone())?;
-    let sdk = ProverSDK::new(args.prover_url.clone(), access_key).await?;
+    let cli = Cli::parse();
+    let access_key = ProverAccessKey::from_hex_string(&cli.prover_access_key.clone())?;
+    let sdk = ProverSDK::new(cli.prover_url.clone(), access_key).await?;
+    if let Some(Commands::Jobs(jobs_args)) = cli.command {
+        return list_jobs(jobs_args, sdk).await;
+    }
+    // Without a subcommand clap requires the prove arguments
+    let args = cli.args.ok_or(ProveErrors::MissingProveArguments)?;
+    if args.dry_run {
+        let result = run(args.clone(), sdk).await?;
+        std::fs::write(args.program_output, serde_json::to_string_pretty(&result)?)?;
+        return Ok(());
+    }
     let job = prove(args.clone(), sdk.clone()).await?;
     if args.wait {
         let job = if args.sse {
@@ -2,30 +2,64 @@ use crate::errors::ProveErrors;
 use crate::validate_input;
 use crate::Args;
 use crate::CairoVersion;
+use prover_sdk::errors::SdkErrors;
 use prover_sdk::sdk::ProverSDK;
 use prover_sdk::{
-    Cairo0CompiledProgram, Cairo0ProverInput, CairoCompiledProgram, CairoProverInput,
+    Cairo0CompiledProgram, Cairo0ProverInput, CairoCompiledProgram, CairoProverInput, JobId,
+    ProverConfig, ProverInput, ProverParams, RunResult,
 };
 use serde_json::Value;
+use std::future::Future;
+use tokio::time::sleep;
+use tracing::info;
 
-pub async fn prove(args: Args, sdk: ProverSDK) -> Result<u64, ProveErrors> {
+const MAX_SUBMIT_ATTEMPTS: usize = 5;
+
+pub async fn prove(args: Args, sdk: ProverSDK) -> Result<JobId, ProveErrors> {
+    let job = match read_input(args)? {
+        ProverInput::Cairo0(data) => submit_with_retry(|| sdk.prove_cairo0(data.clone())).await?,
+        ProverInput::Cairo(data) => submit_with_retry(|| sdk.prove_cairo(data.clone())).await?,
+    };
+    Ok(job)
+}
+
+/// Executes the program on the prover without proving it, for `--dry-run`.
+pub async fn run(args: Args, sdk: ProverSDK) -> Result<RunResult, ProveErrors> {
+    let result = match read_input(args)? {
+        ProverInput::Cairo0(data) => submit_with_retry(|| sdk.run_cairo0(data.clone())).await?,
+        ProverInput::Cairo(data) => submit_with_retry(|| sdk.run_cairo(data.clone())).await?,
+    };
+    Ok(result)
+}
+
+fn read_input(args: Args) -> Result<ProverInput, ProveErrors> {
     let program = std::fs::read_to_string(&args.program_path)?;
-    let proof = match args.cairo_version {
+    let prover_params: Option<ProverParams> = match &args.prover_params {
+        Some(path) => Some(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+        None => None,
+    };
+    let prover_config: Option<ProverConfig> = match &args.prover_config {
+        Some(path) => Some(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+        None => None,
+    };
+    let input = match args.cairo_version {
         CairoVersion::V0 => {
             let input_path = args
                 .program_input_path
                 .ok_or(ProveErrors::MissingProgramInput)?;
-            let input = std::fs::read_to_string(&input_path)?;
+            let input = std::fs::read_to_string(input_path)?;
             let program_serialized: Cairo0CompiledProgram = serde_json::from_str(&program)?;
             let program_input: Value = serde_json::from_str(&input)?;
-            let data = Cairo0ProverInput {
+            ProverInput::Cairo0(Cairo0ProverInput {
                 program: program_serialized,
                 layout: args.layout,
                 program_input,
                 pow_bits: args.pow_bits,
                 n_queries: args.n_queries,
-            };
-            sdk.prove_cairo0(data).await?
+                priority: args.priority,
+                prover_params,
+                prover_config,
+            })
         }
         CairoVersion::V1 => {
             let input = match args.clone().program_input_path {
@@ -36,15 +70,39 @@ pub async fn prove(args: Args, sdk: ProverSDK) -> Result<u64, ProveErrors> {
                 None => args.program_input,
             };
             let program_serialized: CairoCompiledProgram = serde_json::from_str(&program)?;
-            let data = CairoProverInput {
+            ProverInput::Cairo(CairoProverInput {
                 program: program_serialized,
                 layout: args.layout,
                 program_input: input,
                 pow_bits: args.pow_bits,
                 n_queries: args.n_queries,
-            };
-            sdk.prove_cairo(data).await?
+                priority: args.priority,
+                prover_params,
+                prover_config,
+            })
         }
     };
-    Ok(proof)
+    Ok(input)
+}
+
+// Waits out a full prover queue as long as the prover asks, a few times before giving up
+async fn submit_with_retry<T, F, Fut>(mut submit: F) -> Result<T, ProveErrors>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, SdkErrors>>,
+{
+    let mut attempt = 1;
+    loop {
+        match submit().await {
+            Err(e) if attempt < MAX_SUBMIT_ATTEMPTS => {
+                let Some(retry_after) = e.retry_after() else {
+                    return Err(e.into());
+                };
+                info!("{e}, retrying in {} seconds", retry_after.as_secs());
+                sleep(retry_after).await;
+                attempt += 1;
+            }
+            result => return Ok(result?),
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use ed25519_dalek::VerifyingKey;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use starknet_types_core::felt::Felt;
+use std::{collections::BTreeMap, convert::Infallible, fmt::Display, str::FromStr};
 
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
@@ -11,29 +12,386 @@ pub struct JWTResponse {
     pub expiration: u64,
     pub session_key: Option<VerifyingKey>,
 }
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Opaque job identifier.
+///
+/// New jobs get a ULID, integer ids of jobs created by older servers are still accepted
+/// both as JSON numbers and as strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct JobId(String);
+
+impl JobId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for JobId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<u64> for JobId {
+    fn from(id: u64) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl FromStr for JobId {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for JobId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawJobId {
+            Id(String),
+            Legacy(u64),
+        }
+        Ok(match RawJobId::deserialize(deserializer)? {
+            RawJobId::Id(id) => Self(id),
+            RawJobId::Legacy(id) => Self::from(id),
+        })
+    }
+}
+
+/// Opaque identifier of a batch submitted to `POST /prove/batch`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BatchId(String);
+
+impl BatchId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for BatchId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl FromStr for BatchId {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl Display for BatchId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum JobStatus {
     Pending,
     Running,
     Completed,
     Failed,
+    Cancelled,
     Unknown,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+impl FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<JobStatus, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "pending" => Ok(JobStatus::Pending),
+            "running" => Ok(JobStatus::Running),
+            "completed" => Ok(JobStatus::Completed),
+            "failed" => Ok(JobStatus::Failed),
+            "cancelled" => Ok(JobStatus::Cancelled),
+            _ => Err(format!("Invalid job status: {}", input)),
+        }
+    }
+}
+
+/// Scheduling class of a job, queued jobs of a higher class are always picked up first.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum JobPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl FromStr for JobPriority {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<JobPriority, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "low" => Ok(JobPriority::Low),
+            "normal" => Ok(JobPriority::Normal),
+            "high" => Ok(JobPriority::High),
+            _ => Err(format!("Invalid job priority: {}", input)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum CairoVersion {
+    Cairo0,
+    Cairo,
+}
+
+/// Job entry returned by `GET /jobs`, without the proof itself.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobSummary {
+    pub id: JobId,
+    pub status: JobStatus,
+    /// Creation time as a unix timestamp in seconds
+    pub created: u64,
+    pub cairo_version: Option<CairoVersion>,
+    pub layout: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ListJobsResponse {
+    /// Newest jobs first
+    pub jobs: Vec<JobSummary>,
+    /// Pass as `cursor` to fetch the next page, `None` on the last page
+    pub next_cursor: Option<JobId>,
+}
+
+/// Job proving one item of a batch.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BatchJob {
+    pub id: JobId,
+    pub status: JobStatus,
+}
+
+/// Progress of a batch, returned by `POST /prove/batch` and `GET /batch/:id`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchResponse {
+    pub id: BatchId,
+    /// Creation time as a unix timestamp in seconds
+    pub created: u64,
+    /// Job of each item, in the order the items were submitted
+    pub jobs: Vec<BatchJob>,
+    pub pending: usize,
+    pub running: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    /// Jobs forgotten after finishing, reported as [`JobStatus::Unknown`]
+    #[serde(default)]
+    pub expired: usize,
+}
+
+impl BatchResponse {
+    /// Whether every job of the batch finished or expired, whatever the outcome.
+    pub fn is_finished(&self) -> bool {
+        self.completed + self.failed + self.cancelled + self.expired == self.jobs.len()
+    }
+}
+
+/// Program registered with `POST /programs`, as listed by `GET /programs`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProgramSummary {
+    pub program_hash: String,
+    pub cairo_version: CairoVersion,
+    /// Size of the program's JSON in bytes
+    pub size: u64,
+    /// Time the caller registered the program as a unix timestamp in seconds, the earliest
+    /// registration when listing every program
+    pub registered: u64,
+}
+
+/// Step of a job in which it failed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPhase {
+    /// Writing the program and its input to the job directory
+    Prepare,
+    /// Executing the program with the Cairo runner
+    Run,
+    /// Generating the prover parameters from the public input
+    Template,
+    /// Running the prover
+    Prove,
+    /// Reading the proof and extracting the program hash and output
+    Parse,
+}
+
+impl Display for JobPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let phase = match self {
+            JobPhase::Prepare => "prepare",
+            JobPhase::Run => "run",
+            JobPhase::Template => "template",
+            JobPhase::Prove => "prove",
+            JobPhase::Parse => "parse",
+        };
+        write!(f, "{}", phase)
+    }
+}
+
+/// Set on failures caused by a server-side limit rather than by the program itself.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureReason {
+    TimedOut,
+    ResourceExceeded,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobError {
+    pub phase: JobPhase,
+    pub message: String,
+    /// Exit code of the failed subprocess, if the failure came from one
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub reason: Option<FailureReason>,
+}
+
+impl Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed: {}", self.phase, self.message)
+    }
+}
+
+/// Why one field of a submission was rejected.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Output captured from one runner or prover subprocess of a job.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommandLog {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Set when the beginning of the output was dropped to stay within the size limit
+    pub truncated: bool,
+    /// Set when the process was killed for exceeding a limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub killed: Option<FailureReason>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct JobLogs {
+    pub commands: Vec<CommandLog>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProverResult {
     pub proof: String,
     pub serialized_proof: Vec<Felt>,
     pub program_hash: Felt,
     pub program_output: Vec<Felt>,
     pub program_output_hash: Felt,
+    /// Layout the program ran with, `None` in results of older servers
+    #[serde(default)]
+    pub layout: Option<String>,
+}
+
+/// What happened to a job, sent to `GET /sse` clients as an SSE event named after it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "event")]
+pub enum JobEvent {
+    /// The job was accepted and waits for a worker
+    #[serde(rename = "queued")]
+    Queued,
+    /// Number of jobs a worker picks up before this one, sent whenever it changes
+    #[serde(rename = "position")]
+    Position { position: usize },
+    /// The program is being executed to produce the trace
+    #[serde(rename = "running:trace")]
+    RunningTrace,
+    /// The trace is being proven
+    #[serde(rename = "running:prove")]
+    RunningProve,
+    #[serde(rename = "completed")]
+    Completed { result: ProverResult },
+    #[serde(rename = "failed")]
+    Failed {
+        error: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        details: Option<JobError>,
+    },
+    #[serde(rename = "cancelled")]
+    Cancelled,
+}
+
+impl JobEvent {
+    /// Name of the SSE event.
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Queued => "queued",
+            JobEvent::Position { .. } => "position",
+            JobEvent::RunningTrace => "running:trace",
+            JobEvent::RunningProve => "running:prove",
+            JobEvent::Completed { .. } => "completed",
+            JobEvent::Failed { .. } => "failed",
+            JobEvent::Cancelled => "cancelled",
+        }
+    }
+    /// Whether no event follows this one.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            JobEvent::Completed { .. } | JobEvent::Failed { .. } | JobEvent::Cancelled
+        )
+    }
+}
+
+/// Data of an SSE event of `GET /sse`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobEventMessage {
+    /// Also sent as the SSE `id`, pass it as `Last-Event-ID` to resume after it. `None` for
+    /// `position` events, which aren't replayed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    pub job_id: JobId,
+    #[serde(flatten)]
+    pub event: JobEvent,
+}
+
+/// Outcome of executing a program without proving it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunResult {
+    pub program_output: Vec<Felt>,
+    pub n_steps: u64,
+    /// Memory cells used by each builtin segment, by builtin name
+    pub builtins: BTreeMap<String, u64>,
+    pub layout: String,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JobResponse {
     InProgress {
-        id: u64,
+        id: JobId,
         status: JobStatus,
+        /// Number of jobs that will be picked up before this one while it is pending
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        queue_position: Option<usize>,
     },
     Completed {
         result: ProverResult,
@@ -41,5 +399,54 @@ pub enum JobResponse {
     },
     Failed {
         error: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        details: Option<JobError>,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_status_from_str() {
+        assert_eq!("completed".parse(), Ok(JobStatus::Completed));
+        assert_eq!("Running".parse(), Ok(JobStatus::Running));
+        assert!("done".parse::<JobStatus>().is_err());
+    }
+
+    #[test]
+    fn test_job_id_accepts_legacy_integers() {
+        let id: JobId = serde_json::from_str("42").unwrap();
+        assert_eq!(id, JobId::from(42));
+        let id: JobId = serde_json::from_str("\"01J8Z3K6Q4R5S6T7V8W9X0Y1Z2\"").unwrap();
+        assert_eq!(id.as_str(), "01J8Z3K6Q4R5S6T7V8W9X0Y1Z2");
+        assert_eq!(
+            serde_json::to_string(&id).unwrap(),
+            "\"01J8Z3K6Q4R5S6T7V8W9X0Y1Z2\""
+        );
+    }
+
+    #[test]
+    fn test_job_event_message() {
+        let message = JobEventMessage {
+            id: Some(7),
+            job_id: JobId::from(1),
+            event: JobEvent::RunningTrace,
+        };
+        let json = serde_json::to_value(message).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "id": 7, "job_id": "1", "event": "running:trace" })
+        );
+        let message: JobEventMessage = serde_json::from_value(serde_json::json!({
+            "job_id": "1",
+            "event": "position",
+            "position": 3,
+        }))
+        .unwrap();
+        assert!(message.id.is_none());
+        assert!(matches!(message.event, JobEvent::Position { position: 3 }));
+        assert_eq!(message.event.name(), "position");
+    }
+}
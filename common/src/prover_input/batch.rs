@@ -0,0 +1,26 @@
+use super::{CompiledProgram, SubmissionParams};
+use serde::{Deserialize, Serialize};
+
+/// Many inputs of one program submitted together to `POST /prove/batch`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchProverInput<I> {
+    #[serde(flatten)]
+    pub program: BatchProgram,
+    /// Every item becomes a job of its own
+    pub items: Vec<BatchItem<I>>,
+}
+
+/// Program of a batch, embedded as a `program` or referenced by a registered `program_hash`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchProgram {
+    Program(CompiledProgram),
+    ProgramHash(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchItem<I> {
+    pub program_input: I,
+    #[serde(flatten)]
+    pub params: SubmissionParams,
+}
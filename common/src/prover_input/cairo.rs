@@ -1,3 +1,5 @@
+use super::{ProverConfig, ProverParams, SubmissionParams};
+use crate::models::JobPriority;
 use serde::{Deserialize, Serialize};
 use starknet_types_core::felt::Felt;
 
@@ -8,6 +10,32 @@ pub struct CairoProverInput {
     pub layout: String,
     pub n_queries: Option<u32>,
     pub pow_bits: Option<u32>,
+    /// Requested scheduling class, capped at the class allowed for the submitting key
+    #[serde(default)]
+    pub priority: Option<JobPriority>,
+    #[serde(default)]
+    pub prover_params: Option<ProverParams>,
+    #[serde(default)]
+    pub prover_config: Option<ProverConfig>,
+}
+
+impl CairoProverInput {
+    pub fn new(
+        program: CairoCompiledProgram,
+        program_input: Vec<Felt>,
+        params: SubmissionParams,
+    ) -> Self {
+        Self {
+            program,
+            program_input,
+            layout: params.layout,
+            n_queries: params.n_queries,
+            pow_bits: params.pow_bits,
+            priority: params.priority,
+            prover_params: params.prover_params,
+            prover_config: params.prover_config,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1,3 +1,5 @@
+use super::{ProverConfig, ProverParams, SubmissionParams};
+use crate::models::JobPriority;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -7,6 +9,32 @@ pub struct Cairo0ProverInput {
     pub layout: String,
     pub n_queries: Option<u32>,
     pub pow_bits: Option<u32>,
+    /// Requested scheduling class, capped at the class allowed for the submitting key
+    #[serde(default)]
+    pub priority: Option<JobPriority>,
+    #[serde(default)]
+    pub prover_params: Option<ProverParams>,
+    #[serde(default)]
+    pub prover_config: Option<ProverConfig>,
+}
+
+impl Cairo0ProverInput {
+    pub fn new(
+        program: Cairo0CompiledProgram,
+        program_input: serde_json::Value,
+        params: SubmissionParams,
+    ) -> Self {
+        Self {
+            program,
+            program_input,
+            layout: params.layout,
+            n_queries: params.n_queries,
+            pow_bits: params.pow_bits,
+            priority: params.priority,
+            prover_params: params.prover_params,
+            prover_config: params.prover_config,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
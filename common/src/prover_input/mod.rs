@@ -1,8 +1,18 @@
+mod batch;
 mod cairo;
 mod cairo0;
+mod params;
+mod program;
+mod submission;
 
+pub use batch::{BatchItem, BatchProgram, BatchProverInput};
 pub use cairo::{CairoCompiledProgram, CairoProverInput};
 pub use cairo0::{Cairo0CompiledProgram, Cairo0ProverInput};
+pub use params::{
+    CachedLdeConfig, ChannelHash, CommitmentHash, PowHash, ProverConfig, ProverParams, ProverPreset,
+};
+pub use program::{CompiledProgram, ProgramSubmission, RegisteredProgramInput};
+pub use submission::SubmissionParams;
 
 #[derive(Debug)]
 pub enum ProverInput {
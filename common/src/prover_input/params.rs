@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+/// Stone parameters of a proof, unset fields come from the preset or the server's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProverParams {
+    /// Parameters expected by a known verifier, the other fields override it
+    pub preset: Option<ProverPreset>,
+    pub channel_hash: Option<ChannelHash>,
+    pub commitment_hash: Option<CommitmentHash>,
+    pub pow_hash: Option<PowHash>,
+    /// Log of the blowup factor, each query is worth this many bits of security
+    pub log_n_cosets: Option<u32>,
+    /// Degree bound of the last FRI layer, a power of two
+    pub last_layer_degree_bound: Option<u32>,
+    /// Merkle tree layers hashed with `verifier_friendly_commitment_hash`, counted from the top
+    pub n_verifier_friendly_commitment_layers: Option<u32>,
+    pub verifier_friendly_channel_updates: Option<bool>,
+    pub verifier_friendly_commitment_hash: Option<CommitmentHash>,
+}
+
+/// Per job overrides of the Stone prover config, trading memory for speed on large traces.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProverConfig {
+    pub constraint_polynomial_task_size: Option<u32>,
+    /// Merkle layers recomputed instead of kept in memory
+    pub n_out_of_memory_merkle_layers: Option<u32>,
+    pub cached_lde_config: Option<CachedLdeConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedLdeConfig {
+    /// Keeps the whole low degree extension in memory instead of recomputing it
+    pub store_full_lde: bool,
+    pub use_fft_for_eval: bool,
+}
+
+/// Parameter sets matching the on-chain verifiers proofs are usually sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProverPreset {
+    /// Poseidon channel and commitments, for the Cairo verifier on Starknet
+    StarknetRecursive,
+    /// Keccak channel and commitments, for the Solidity verifier on Ethereum
+    EthereumVerifierFriendly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelHash {
+    Poseidon3,
+    Keccak256,
+    Blake256,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitmentHash {
+    Poseidon3,
+    Blake256Masked160Lsb,
+    Keccak256Masked160Msb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowHash {
+    Keccak256,
+    Blake256,
+}
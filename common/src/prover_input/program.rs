@@ -0,0 +1,92 @@
+use super::{
+    Cairo0CompiledProgram, CairoCompiledProgram, ProverConfig, ProverParams, SubmissionParams,
+};
+use crate::models::{CairoVersion, JobPriority};
+use serde::{Deserialize, Serialize};
+
+/// A compiled program of either Cairo version, as registered with `POST /programs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "cairo_version", content = "program")]
+pub enum CompiledProgram {
+    Cairo0(Cairo0CompiledProgram),
+    Cairo(CairoCompiledProgram),
+}
+
+impl CompiledProgram {
+    pub fn cairo_version(&self) -> CairoVersion {
+        match self {
+            CompiledProgram::Cairo0(_) => CairoVersion::Cairo0,
+            CompiledProgram::Cairo(_) => CairoVersion::Cairo,
+        }
+    }
+}
+
+impl TryFrom<CompiledProgram> for Cairo0CompiledProgram {
+    type Error = CompiledProgram;
+
+    fn try_from(program: CompiledProgram) -> Result<Self, Self::Error> {
+        match program {
+            CompiledProgram::Cairo0(program) => Ok(program),
+            program => Err(program),
+        }
+    }
+}
+
+impl TryFrom<CompiledProgram> for CairoCompiledProgram {
+    type Error = CompiledProgram;
+
+    fn try_from(program: CompiledProgram) -> Result<Self, Self::Error> {
+        match program {
+            CompiledProgram::Cairo(program) => Ok(program),
+            program => Err(program),
+        }
+    }
+}
+
+/// A JSON submission, which embeds its `program` or references a registered one with
+/// `program_hash`.
+///
+/// The fields are listed rather than flattened, so that the program is deserialized straight
+/// into `P` without the request being buffered first.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ProgramSubmission<P, I> {
+    pub program: Option<P>,
+    pub program_hash: Option<String>,
+    pub program_input: I,
+    pub layout: String,
+    #[serde(default)]
+    pub n_queries: Option<u32>,
+    #[serde(default)]
+    pub pow_bits: Option<u32>,
+    #[serde(default)]
+    pub priority: Option<JobPriority>,
+    #[serde(default)]
+    pub prover_params: Option<ProverParams>,
+    #[serde(default)]
+    pub prover_config: Option<ProverConfig>,
+}
+
+impl<P, I> ProgramSubmission<P, I> {
+    /// Splits off the program, the hash referencing it and the program input from the rest.
+    pub fn into_parts(self) -> (Option<P>, Option<String>, I, SubmissionParams) {
+        let params = SubmissionParams {
+            layout: self.layout,
+            n_queries: self.n_queries,
+            pow_bits: self.pow_bits,
+            priority: self.priority,
+            prover_params: self.prover_params,
+            prover_config: self.prover_config,
+        };
+        (self.program, self.program_hash, self.program_input, params)
+    }
+}
+
+/// A submission referencing a registered program by its hash instead of embedding it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisteredProgramInput<I> {
+    /// Hash returned when the program was registered
+    pub program_hash: String,
+    pub program_input: I,
+    #[serde(flatten)]
+    pub params: SubmissionParams,
+}
@@ -0,0 +1,21 @@
+use super::{ProverConfig, ProverParams};
+use crate::models::JobPriority;
+use serde::{Deserialize, Serialize};
+
+/// Fields of a submission besides the program and its input, sent as the `params` part of a
+/// multipart upload.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubmissionParams {
+    pub layout: String,
+    #[serde(default)]
+    pub n_queries: Option<u32>,
+    #[serde(default)]
+    pub pow_bits: Option<u32>,
+    #[serde(default)]
+    pub priority: Option<JobPriority>,
+    #[serde(default)]
+    pub prover_params: Option<ProverParams>,
+    #[serde(default)]
+    pub prover_config: Option<ProverConfig>,
+}
@@ -1,3 +1,4 @@
+use crate::models::{JobId, JobStatus};
 use ed25519_dalek::{Signature, VerifyingKey};
 use serde::{Deserialize, Serialize};
 
@@ -22,3 +23,16 @@ pub struct AddKeyRequest {
     pub authority: VerifyingKey,
     pub new_key: VerifyingKey,
 }
+
+/// Query parameters of `GET /jobs`, every filter is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListJobsQuery {
+    pub status: Option<JobStatus>,
+    /// Only jobs created at or after this unix timestamp (seconds)
+    pub created_after: Option<u64>,
+    /// Only jobs created before this unix timestamp (seconds)
+    pub created_before: Option<u64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<JobId>,
+    pub limit: Option<usize>,
+}
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,14 +9,28 @@ pub enum SdkErrors {
     UrlParseError(#[from] url::ParseError),
     #[error("Prover response error: {0}")]
     ProveResponseError(String),
+    #[error("Run response error: {0}")]
+    RunResponseError(String),
     #[error("Get Job response error: {0}")]
     GetJobResponseError(String),
+    #[error("Cancel Job response error: {0}")]
+    CancelJobResponseError(String),
+    #[error("Job Logs response error: {0}")]
+    JobLogsResponseError(String),
+    #[error("List Jobs response error: {0}")]
+    ListJobsResponseError(String),
+    #[error("Program response error: {0}")]
+    ProgramResponseError(String),
+    #[error("Batch response error: {0}")]
+    BatchResponseError(String),
     #[error("Prefix error: {0}")]
     PrefixError(String),
     #[error("Nonce Request error: {0}")]
     NonceRequestFailed(String),
     #[error(transparent)]
     Parse(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
     #[error("Nonce not found")]
     NonceNotFound,
     #[error("Validate Signature response error: {0}")]
@@ -34,4 +49,24 @@ pub enum SdkErrors {
     VerifyResponseError(String),
     #[error("Invalid key")]
     InvalidKey,
+    #[error("Prover queue is full, retry after {retry_after:?}")]
+    QueueFull { retry_after: Option<Duration> },
+    #[error("Prover is unavailable: {0}")]
+    ServiceUnavailable(String),
+}
+
+// Used when the prover asks to back off without saying for how long
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+impl SdkErrors {
+    /// How long to wait before submitting again, `None` when retrying won't help.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            SdkErrors::QueueFull { retry_after } => {
+                Some(retry_after.unwrap_or(DEFAULT_RETRY_AFTER))
+            }
+            SdkErrors::ServiceUnavailable(_) => Some(DEFAULT_RETRY_AFTER),
+            _ => None,
+        }
+    }
 }
@@ -3,5 +3,10 @@ pub mod errors;
 pub mod sdk;
 pub mod sdk_builder;
 
-pub use common::models::{JobResponse, ProverResult};
+pub use common::models::{
+    BatchId, BatchJob, BatchResponse, CairoVersion, CommandLog, FieldError, JobError, JobEvent,
+    JobEventMessage, JobId, JobLogs, JobPriority, JobResponse, JobStatus, JobSummary,
+    ListJobsResponse, ProgramSummary, ProverResult, RunResult,
+};
 pub use common::prover_input::*;
+pub use common::requests::ListJobsQuery;
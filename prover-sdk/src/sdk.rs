@@ -1,12 +1,26 @@
 use crate::{access_key::ProverAccessKey, errors::SdkErrors, sdk_builder::ProverSDKBuilder};
 use common::{
-    prover_input::{Cairo0ProverInput, CairoProverInput, ProverInput},
-    requests::AddKeyRequest,
+    models::{
+        BatchId, BatchResponse, JobEventMessage, JobId, JobLogs, ListJobsResponse, ProgramSummary,
+        RunResult,
+    },
+    prover_input::{
+        BatchProverInput, Cairo0ProverInput, CairoProverInput, CompiledProgram, ProverInput,
+        RegisteredProgramInput, SubmissionParams,
+    },
+    requests::{AddKeyRequest, ListJobsQuery},
 };
 use ed25519_dalek::{ed25519::signature::SignerMut, VerifyingKey};
-use futures::StreamExt;
-use reqwest::{Client, Response};
-use serde::Deserialize;
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use reqwest::{
+    header::RETRY_AFTER,
+    multipart::{Form, Part},
+    Body, Client, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::{collections::VecDeque, path::Path, time::Duration};
+use tokio_util::io::ReaderStream;
 use url::Url;
 #[derive(Debug, Clone)]
 /// ProverSDK is a struct representing a client for interacting with the Prover service.
@@ -14,16 +28,30 @@ pub struct ProverSDK {
     pub client: Client,
     pub prover_cairo0: Url,
     pub prover_cairo: Url,
+    pub upload_cairo0: Url,
+    pub upload_cairo: Url,
+    pub runner_cairo0: Url,
+    pub runner_cairo: Url,
     pub verify: Url,
     pub get_job: Url,
+    pub job: Url,
+    pub jobs: Url,
+    pub prover_batch: Url,
+    pub batch: Url,
+    pub programs: Url,
     pub register: Url,
     pub sse: Url,
     pub authority: ProverAccessKey,
 }
 
 #[derive(Deserialize)]
-pub struct JobId {
-    pub job_id: u64,
+pub struct ProveResponse {
+    pub job_id: JobId,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterProgramResponse {
+    pub program_hash: String,
 }
 
 impl ProverSDK {
@@ -42,33 +70,217 @@ impl ProverSDK {
             .build()
     }
 
-    pub async fn prove_cairo0(&self, data: Cairo0ProverInput) -> Result<u64, SdkErrors> {
-        self.prove(ProverInput::Cairo0(data), self.prover_cairo0.clone())
+    pub async fn prove_cairo0(&self, data: Cairo0ProverInput) -> Result<JobId, SdkErrors> {
+        self.prove(
+            &ProverInput::Cairo0(data).to_json_value(),
+            self.prover_cairo0.clone(),
+        )
+        .await
+    }
+
+    pub async fn prove_cairo(&self, data: CairoProverInput) -> Result<JobId, SdkErrors> {
+        self.prove(
+            &ProverInput::Cairo(data).to_json_value(),
+            self.prover_cairo.clone(),
+        )
+        .await
+    }
+
+    /// Proves a Cairo 0 program registered with `register_program`.
+    pub async fn prove_cairo0_registered(
+        &self,
+        data: RegisteredProgramInput<serde_json::Value>,
+    ) -> Result<JobId, SdkErrors> {
+        self.prove(&data, self.prover_cairo0.clone()).await
+    }
+
+    /// Proves a Cairo program registered with `register_program`.
+    pub async fn prove_cairo_registered(
+        &self,
+        data: RegisteredProgramInput<Vec<Felt>>,
+    ) -> Result<JobId, SdkErrors> {
+        self.prove(&data, self.prover_cairo.clone()).await
+    }
+
+    /// Submits a Cairo 0 program and its input from JSON files, streaming them to the prover.
+    ///
+    /// Unlike `prove_cairo0` the files are never loaded in memory, for programs too large to
+    /// submit in a single JSON body.
+    pub async fn prove_cairo0_files(
+        &self,
+        program: &Path,
+        program_input: &Path,
+        params: &SubmissionParams,
+    ) -> Result<JobId, SdkErrors> {
+        self.upload(program, program_input, params, self.upload_cairo0.clone())
             .await
     }
 
-    pub async fn prove_cairo(&self, data: CairoProverInput) -> Result<u64, SdkErrors> {
-        self.prove(ProverInput::Cairo(data), self.prover_cairo.clone())
+    /// Submits a Cairo program and its input from JSON files, streaming them to the prover.
+    pub async fn prove_cairo_files(
+        &self,
+        program: &Path,
+        program_input: &Path,
+        params: &SubmissionParams,
+    ) -> Result<JobId, SdkErrors> {
+        self.upload(program, program_input, params, self.upload_cairo.clone())
             .await
     }
 
-    async fn prove(&self, data: ProverInput, url: Url) -> Result<u64, SdkErrors> {
+    /// Submits many inputs of one program at once, each item becomes a job of its own.
+    ///
+    /// The batch is accepted as a whole or rejected, the response holds the job of every item.
+    pub async fn prove_batch<I: Serialize>(
+        &self,
+        batch: &BatchProverInput<I>,
+    ) -> Result<BatchResponse, SdkErrors> {
         let response = self
             .client
-            .post(url.clone())
-            .json(&data.to_json_value())
+            .post(self.prover_batch.clone())
+            .json(batch)
             .send()
             .await?;
+        let response = check_capacity(response, SdkErrors::BatchResponseError).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Current status of every job of a batch.
+    pub async fn get_batch(&self, batch_id: &BatchId) -> Result<BatchResponse, SdkErrors> {
+        let url = format!("{}/{}", self.batch.clone().as_str(), batch_id);
+        let response = self.client.get(url).send().await?;
 
         if !response.status().is_success() {
             let response_data: String = response.text().await?;
             tracing::error!("{}", response_data);
-            return Err(SdkErrors::ProveResponseError(response_data));
+            return Err(SdkErrors::BatchResponseError(response_data));
         }
+        Ok(response.json().await?)
+    }
+
+    /// Streams the final event of every job of a batch as it finishes, the jobs that already
+    /// finished first.
+    ///
+    /// The stream ends once every job completed, failed or was cancelled.
+    pub async fn batch_completions(
+        &self,
+        batch_id: &BatchId,
+    ) -> Result<impl Stream<Item = Result<JobEventMessage, SdkErrors>>, SdkErrors> {
+        let url = format!("{}?batch_id={}", self.sse.clone().as_str(), batch_id);
+        let events = self.events(url, None).await?;
+        // The server also reports jobs that are queued or start running
+        Ok(events.filter(|message| {
+            future::ready(
+                message
+                    .as_ref()
+                    .map_or(true, |message| message.event.is_final()),
+            )
+        }))
+    }
+
+    /// Streams the events of a job, starting with its current state, until it completed, failed
+    /// or was cancelled.
+    ///
+    /// Pass the `id` of the last event received to resume a stream that broke off, the events
+    /// sent in between are replayed as long as the server still holds them.
+    pub async fn job_events(
+        &self,
+        job_id: &JobId,
+        last_event_id: Option<u64>,
+    ) -> Result<impl Stream<Item = Result<JobEventMessage, SdkErrors>>, SdkErrors> {
+        let url = format!("{}?job_id={}", self.sse.clone().as_str(), job_id);
+        self.events(url, last_event_id).await
+    }
+
+    async fn events(
+        &self,
+        url: String,
+        last_event_id: Option<u64>,
+    ) -> Result<impl Stream<Item = Result<JobEventMessage, SdkErrors>>, SdkErrors> {
+        let mut request = self.client.get(url);
+        if let Some(id) = last_event_id {
+            request = request.header("Last-Event-ID", id.to_string());
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(SdkErrors::SSEError(format!(
+                "Failed to get SSE with status code: {}",
+                response.status(),
+            )));
+        }
+
+        let state = (
+            response.bytes_stream(),
+            Vec::new(),
+            VecDeque::<String>::new(),
+        );
+        Ok(stream::unfold(
+            state,
+            |(mut bytes, mut buffer, mut events)| async move {
+                loop {
+                    if let Some(data) = events.pop_front() {
+                        let message = serde_json::from_str(&data).map_err(SdkErrors::from);
+                        return Some((message, (bytes, buffer, events)));
+                    }
+                    match bytes.next().await? {
+                        Ok(chunk) => {
+                            buffer.extend_from_slice(&chunk);
+                            events.extend(take_events(&mut buffer));
+                        }
+                        Err(e) => return Some((Err(e.into()), (bytes, buffer, events))),
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Executes the program without proving it, returning its output and resource usage.
+    pub async fn run_cairo0(&self, data: Cairo0ProverInput) -> Result<RunResult, SdkErrors> {
+        self.run(ProverInput::Cairo0(data), self.runner_cairo0.clone())
+            .await
+    }
+
+    /// Executes the program without proving it, returning its output and resource usage.
+    pub async fn run_cairo(&self, data: CairoProverInput) -> Result<RunResult, SdkErrors> {
+        self.run(ProverInput::Cairo(data), self.runner_cairo.clone())
+            .await
+    }
+
+    async fn prove(&self, data: &impl Serialize, url: Url) -> Result<JobId, SdkErrors> {
+        let response = self.client.post(url.clone()).json(data).send().await?;
+        let response = check_capacity(response, SdkErrors::ProveResponseError).await?;
         let response_data = response.text().await?;
-        let job = serde_json::from_str::<JobId>(&response_data)?;
+        let job = serde_json::from_str::<ProveResponse>(&response_data)?;
         Ok(job.job_id)
     }
+
+    async fn upload(
+        &self,
+        program: &Path,
+        program_input: &Path,
+        params: &SubmissionParams,
+        url: Url,
+    ) -> Result<JobId, SdkErrors> {
+        let form = Form::new()
+            .part("program", file_part(program).await?)
+            .part("program_input", file_part(program_input).await?)
+            .text("params", serde_json::to_string(params)?);
+        let response = self.client.post(url).multipart(form).send().await?;
+        let response = check_capacity(response, SdkErrors::ProveResponseError).await?;
+        let response_data = response.text().await?;
+        let job = serde_json::from_str::<ProveResponse>(&response_data)?;
+        Ok(job.job_id)
+    }
+
+    async fn run(&self, data: ProverInput, url: Url) -> Result<RunResult, SdkErrors> {
+        let response = self
+            .client
+            .post(url)
+            .json(&data.to_json_value())
+            .send()
+            .await?;
+        let response = check_capacity(response, SdkErrors::RunResponseError).await?;
+        Ok(response.json().await?)
+    }
     pub async fn verify(self, proof: String) -> Result<String, SdkErrors> {
         let response = self
             .client
@@ -79,7 +291,7 @@ impl ProverSDK {
         let response_data = response.text().await?;
         Ok(response_data)
     }
-    pub async fn get_job(&self, job_id: u64) -> Result<Response, SdkErrors> {
+    pub async fn get_job(&self, job_id: &JobId) -> Result<Response, SdkErrors> {
         let url = format!("{}/{}", self.get_job.clone().as_str(), job_id);
         let response = self.client.get(url).send().await?;
 
@@ -90,6 +302,84 @@ impl ProverSDK {
         }
         Ok(response)
     }
+    pub async fn cancel_job(&self, job_id: &JobId) -> Result<(), SdkErrors> {
+        let url = format!("{}/{}", self.job.clone().as_str(), job_id);
+        let response = self.client.delete(url).send().await?;
+
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::CancelJobResponseError(response_data));
+        }
+        Ok(())
+    }
+    /// Output of the runner and prover subprocesses of a job.
+    pub async fn job_logs(&self, job_id: &JobId) -> Result<JobLogs, SdkErrors> {
+        let url = format!("{}/{}/logs", self.job.clone().as_str(), job_id);
+        let response = self.client.get(url).send().await?;
+
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::JobLogsResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+    pub async fn list_jobs(&self, query: &ListJobsQuery) -> Result<ListJobsResponse, SdkErrors> {
+        let response = self
+            .client
+            .get(self.jobs.clone())
+            .query(query)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::ListJobsResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+    /// Uploads a compiled program once, returning the hash to prove it by afterwards.
+    pub async fn register_program(&self, program: &CompiledProgram) -> Result<String, SdkErrors> {
+        let response = self
+            .client
+            .post(self.programs.clone())
+            .json(program)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::ProgramResponseError(response_data));
+        }
+        let response: RegisterProgramResponse = response.json().await?;
+        Ok(response.program_hash)
+    }
+    /// Programs registered with the access key.
+    pub async fn list_programs(&self) -> Result<Vec<ProgramSummary>, SdkErrors> {
+        let response = self.client.get(self.programs.clone()).send().await?;
+
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::ProgramResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+    /// Removes the access key's registration of a program.
+    pub async fn delete_program(&self, program_hash: &str) -> Result<(), SdkErrors> {
+        let url = format!("{}/{}", self.programs.clone().as_str(), program_hash);
+        let response = self.client.delete(url).send().await?;
+
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::ProgramResponseError(response_data));
+        }
+        Ok(())
+    }
     pub async fn register(&mut self, key: VerifyingKey) -> Result<(), SdkErrors> {
         let signature = self.authority.0.sign(key.as_bytes());
         let request = AddKeyRequest {
@@ -111,18 +401,85 @@ impl ProverSDK {
         }
         Ok(())
     }
-    pub async fn sse(&self, job_id: u64) -> Result<(), SdkErrors> {
-        let url = format!("{}?job_id={}", self.sse.clone().as_str(), job_id);
-        let response = self.client.get(url).send().await?;
-        if !response.status().is_success() {
-            return Err(SdkErrors::SSEError(format!(
-                "Failed to get SSE with status code: {}",
-                response.status(),
-            )));
+    /// Waits until the events of a job end, discarding them.
+    #[deprecated(note = "use job_events")]
+    pub async fn sse(&self, job_id: &JobId) -> Result<(), SdkErrors> {
+        let events = self.job_events(job_id, None).await?;
+        events.try_for_each(|_| future::ok(())).await
+    }
+}
+
+// Splits the complete server-sent events off the front of `buffer`, returning their data
+fn take_events(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut events = Vec::new();
+    while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+        let event: Vec<u8> = buffer.drain(..end + 2).collect();
+        let data: Vec<String> = String::from_utf8_lossy(&event)
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.trim_start().to_string())
+            .collect();
+        // Keep-alive comments carry no data
+        if !data.is_empty() {
+            events.push(data.join("\n"));
         }
+    }
+    events
+}
 
-        let mut stream = response.bytes_stream();
-        while let Some(_item) = stream.next().await {}
-        Ok(())
+// Streams a file as a part of a multipart submission
+async fn file_part(path: &Path) -> Result<Part, SdkErrors> {
+    let file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(
+        Part::stream_with_length(Body::wrap_stream(ReaderStream::new(file)), len)
+            .file_name(file_name)
+            .mime_str("application/json")?,
+    )
+}
+
+// Turns a rejected submission into an error, telling a full queue apart from a bad request
+async fn check_capacity(
+    response: Response,
+    error: fn(String) -> SdkErrors,
+) -> Result<Response, SdkErrors> {
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok()?.parse().ok())
+                .map(Duration::from_secs);
+            return Err(SdkErrors::QueueFull { retry_after });
+        }
+        StatusCode::SERVICE_UNAVAILABLE => {
+            return Err(SdkErrors::ServiceUnavailable(response.text().await?));
+        }
+        status if !status.is_success() => {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(error(response_data));
+        }
+        _ => {}
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_events() {
+        let mut buffer = b"data: first\n\n:\n\ndata: sec".to_vec();
+        assert_eq!(take_events(&mut buffer), vec!["first".to_string()]);
+        assert_eq!(buffer, b"data: sec");
+        buffer.extend_from_slice(b"ond\n\n");
+        assert_eq!(take_events(&mut buffer), vec!["second".to_string()]);
+        assert!(buffer.is_empty());
     }
 }
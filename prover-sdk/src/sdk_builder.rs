@@ -157,8 +157,17 @@ impl ProverSDKBuilder {
             client,
             prover_cairo0: self.base_url.join("prove/cairo0")?,
             prover_cairo: self.base_url.join("prove/cairo")?,
+            upload_cairo0: self.base_url.join("prove/cairo0/upload")?,
+            upload_cairo: self.base_url.join("prove/cairo/upload")?,
+            runner_cairo0: self.base_url.join("run/cairo0")?,
+            runner_cairo: self.base_url.join("run/cairo")?,
             verify: self.base_url.join("verify")?,
             get_job: self.base_url.join("get-job")?,
+            job: self.base_url.join("job")?,
+            jobs: self.base_url.join("jobs")?,
+            prover_batch: self.base_url.join("prove/batch")?,
+            batch: self.base_url.join("batch")?,
+            programs: self.base_url.join("programs")?,
             register: self.base_url.join("register")?,
             sse: self.base_url.join("sse")?,
             authority: signing_key,
@@ -1,15 +1,17 @@
-use common::models::{JobResponse, ProverResult};
+use common::models::{JobEvent, JobId, ProverResult};
+use futures::StreamExt;
 use prover_sdk::sdk::ProverSDK;
 
-pub async fn fetch_job(sdk: ProverSDK, job: u64) -> Option<ProverResult> {
+pub async fn fetch_job(sdk: ProverSDK, job: JobId) -> Option<ProverResult> {
     println!("Job ID: {}", job);
-    sdk.sse(job).await.unwrap();
-    let response = sdk.get_job(job).await.unwrap();
-    let response = response.text().await.unwrap();
-    let json_response: JobResponse = serde_json::from_str(&response).unwrap();
-
-    if let JobResponse::Completed { result, .. } = json_response {
-        return Some(result);
+    let events = sdk.job_events(&job, None).await.unwrap();
+    let mut events = std::pin::pin!(events);
+    while let Some(message) = events.next().await {
+        match message.unwrap().event {
+            JobEvent::Completed { result } => return Some(result),
+            event if event.is_final() => return None,
+            _ => {}
+        }
     }
     None
 }
@@ -30,6 +30,9 @@ async fn test_cairo_prove() {
         program_input,
         n_queries: Some(16),
         pow_bits: Some(20),
+        priority: None,
+        prover_params: None,
+        prover_config: None,
     };
     let job = sdk.prove_cairo(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
@@ -76,6 +79,9 @@ async fn test_cairo0_prove() {
         program_input,
         n_queries: Some(16),
         pow_bits: Some(20),
+        priority: None,
+        prover_params: None,
+        prover_config: None,
     };
     let job = sdk.prove_cairo0(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
@@ -125,6 +131,9 @@ async fn test_cairo_multi_prove() {
         program_input,
         n_queries: Some(16),
         pow_bits: Some(20),
+        priority: None,
+        prover_params: None,
+        prover_config: None,
     };
     let job1 = sdk.prove_cairo(data.clone()).await.unwrap();
     let job2 = sdk.prove_cairo(data.clone()).await.unwrap();
@@ -139,3 +148,31 @@ async fn test_cairo_multi_prove() {
     let result = sdk.clone().verify(result.unwrap().proof).await.unwrap();
     assert_eq!("true", result);
 }
+#[tokio::test]
+async fn test_cairo0_run() {
+    let private_key = std::env::var("PRIVATE_KEY").unwrap();
+    let url = std::env::var("PROVER_URL").unwrap();
+    let access_key = ProverAccessKey::from_hex_string(&private_key).unwrap();
+    let url = Url::parse(&url).unwrap();
+    let sdk = ProverSDK::new(url, access_key).await.unwrap();
+    let program = std::fs::read_to_string("../examples/cairo0/fibonacci_compiled.json").unwrap();
+    let program: Cairo0CompiledProgram = serde_json::from_str(&program).unwrap();
+    let program_input_string = std::fs::read_to_string("../examples/cairo0/input.json").unwrap();
+    let program_input: Value = serde_json::from_str(&program_input_string).unwrap();
+    let data = Cairo0ProverInput {
+        program,
+        layout: "recursive".to_string(),
+        program_input,
+        n_queries: None,
+        pow_bits: None,
+        priority: None,
+        prover_params: None,
+        prover_config: None,
+    };
+    let result = sdk.run_cairo0(data).await.unwrap();
+
+    assert_eq!(result.program_output.len(), 2);
+    assert_eq!(result.layout, "recursive");
+    assert!(result.n_steps > 0);
+    assert!(result.builtins.contains_key("output"));
+}
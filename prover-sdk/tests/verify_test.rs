@@ -40,6 +40,9 @@ async fn test_verify_valid_proof() {
         program_input,
         n_queries: Some(16),
         pow_bits: Some(20),
+        priority: None,
+        prover_params: None,
+        prover_config: None,
     };
     let job = sdk.clone().prove_cairo(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
@@ -31,4 +31,7 @@ pub enum AuthError {
 
     #[error("Unauthorized")]
     Unauthorized,
+
+    #[error("Forbidden")]
+    Forbidden,
 }
@@ -61,12 +61,20 @@ where
         Ok(token_data.claims)
     }
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
     pub session_key: VerifyingKey,
 }
+impl Claims {
+    /// Returns true when the token was issued to one of the `admin_keys`.
+    pub fn is_admin(&self, admin_keys: &[VerifyingKey]) -> bool {
+        admin_keys
+            .iter()
+            .any(|key| prefix_hex::encode(key.to_bytes()) == self.sub)
+    }
+}
 impl Display for Claims {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "sub: {}", self.sub)
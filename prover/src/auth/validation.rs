@@ -76,10 +76,9 @@ mod tests {
     use tokio::sync::Mutex;
 
     use crate::{
-        auth::{authorizer::Authorizer, nonce::Nonce, validate_signature},
+        auth::{nonce::Nonce, validate_signature},
         errors::ProverError,
         server::AppState,
-        threadpool::ThreadPool,
     };
 
     fn generate_signing_key() -> SigningKey {
@@ -112,15 +111,8 @@ mod tests {
         nonces.lock().await.insert(nonce_string.clone(), public_key);
 
         let app_state = AppState {
-            jwt_secret_key: "secret".to_string(),
-            job_store: Default::default(),
-            message_expiration_time: 100,
-            session_expiration_time: 100,
-            thread_pool: Arc::new(Mutex::new(ThreadPool::new(1))),
             nonces,
-            authorizer: Authorizer::Open,
-            admin_keys: vec![generate_verifying_key(&generate_signing_key())],
-            sse_tx: Arc::new(Mutex::new(tokio::sync::broadcast::channel(100).0)),
+            ..AppState::for_tests().await
         };
 
         let result = validate_signature(State(app_state), Json(payload)).await;
@@ -155,15 +147,8 @@ mod tests {
             .insert(nonce_string.clone(), false_public_key);
 
         let app_state = AppState {
-            jwt_secret_key: "secret".to_string(),
-            job_store: Default::default(),
-            message_expiration_time: 100,
-            session_expiration_time: 100,
-            thread_pool: Arc::new(Mutex::new(ThreadPool::new(1))),
             nonces,
-            authorizer: Authorizer::Open,
-            admin_keys: vec![generate_verifying_key(&generate_signing_key())],
-            sse_tx: Arc::new(Mutex::new(tokio::sync::broadcast::channel(100).0)),
+            ..AppState::for_tests().await
         };
 
         let result = validate_signature(State(app_state), Json(payload)).await;
@@ -195,15 +180,8 @@ mod tests {
             Arc::new(Mutex::new(HashMap::new())); // Empty nonces map
 
         let app_state = AppState {
-            jwt_secret_key: "secret".to_string(),
-            job_store: Default::default(),
-            message_expiration_time: 100,
-            session_expiration_time: 100,
-            thread_pool: Arc::new(Mutex::new(ThreadPool::new(1))),
             nonces,
-            authorizer: Authorizer::Open,
-            admin_keys: vec![generate_verifying_key(&generate_signing_key())],
-            sse_tx: Arc::new(Mutex::new(tokio::sync::broadcast::channel(100).0)),
+            ..AppState::for_tests().await
         };
 
         let result = validate_signature(State(app_state), Json(payload)).await;
@@ -236,15 +214,8 @@ mod tests {
         nonces.lock().await.insert(nonce_string.clone(), public_key);
 
         let app_state = AppState {
-            jwt_secret_key: "secret".to_string(),
-            job_store: Default::default(),
-            message_expiration_time: 100,
-            session_expiration_time: 100,
-            thread_pool: Arc::new(Mutex::new(ThreadPool::new(1))),
             nonces,
-            authorizer: Authorizer::Open,
-            admin_keys: vec![generate_verifying_key(&generate_signing_key())],
-            sse_tx: Arc::new(Mutex::new(tokio::sync::broadcast::channel(100).0)),
+            ..AppState::for_tests().await
         };
 
         let result = validate_signature(State(app_state), Json(payload)).await;
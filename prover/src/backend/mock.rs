@@ -0,0 +1,147 @@
+use super::ProverBackend;
+use crate::{
+    errors::ProverError,
+    threadpool::{limits::ProcessLimits, prove::ProvePaths},
+    utils::public_input::PublicInput,
+};
+use common::models::{CairoVersion, CommandLog, ProverResult};
+use common::prover_input::ProverConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use starknet_types_core::felt::Felt;
+use std::path::Path;
+use tokio::{fs, sync::watch};
+
+/// Wraps the public input into a fake proof, so that jobs complete without the Stone binaries.
+///
+/// Program output is read from the public memory, as Stone would, the hashes are a
+/// non-cryptographic digest. Proofs only verify with this backend.
+#[derive(Debug, Clone, Copy)]
+pub struct MockBackend;
+
+#[derive(Serialize, Deserialize)]
+struct MockProof {
+    public_input: Value,
+    digest: Felt,
+}
+
+impl ProverBackend for MockBackend {
+    async fn prove(
+        &self,
+        paths: &ProvePaths,
+        _config: Option<&ProverConfig>,
+        _limits: ProcessLimits,
+        _cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError> {
+        let public_input: Value =
+            serde_json::from_str(&fs::read_to_string(&paths.public_input_file).await?)?;
+        let proof = MockProof {
+            digest: digest(&serde_json::to_vec(&public_input)?),
+            public_input,
+        };
+        fs::write(&paths.proof_path, serde_json::to_string(&proof)?).await?;
+        Ok(CommandLog {
+            command: "mock".to_string(),
+            exit_code: Some(0),
+            stdout: String::new(),
+            stderr: String::new(),
+            truncated: false,
+            killed: None,
+        })
+    }
+
+    async fn verify(&self, proof_file: &Path) -> Result<bool, ProverError> {
+        let proof = fs::read_to_string(proof_file).await?;
+        let Ok(proof) = serde_json::from_str::<MockProof>(&proof) else {
+            return Ok(false);
+        };
+        Ok(proof.digest == digest(&serde_json::to_vec(&proof.public_input)?))
+    }
+
+    fn extract(
+        &self,
+        proof: String,
+        _cairo_version: CairoVersion,
+    ) -> Result<ProverResult, ProverError> {
+        let mock_proof: MockProof = serde_json::from_str(&proof)?;
+        let public_input: PublicInput = serde_json::from_value(mock_proof.public_input)?;
+        let program = public_input.segment("program")?;
+        let program_output = public_input.segment("output")?;
+        Ok(ProverResult {
+            proof,
+            serialized_proof: vec![mock_proof.digest],
+            program_hash: digest_felts(&program),
+            program_output_hash: digest_felts(&program_output),
+            program_output,
+            layout: None,
+        })
+    }
+}
+
+fn digest_felts(felts: &[Felt]) -> Felt {
+    let bytes: Vec<u8> = felts.iter().flat_map(Felt::to_bytes_be).collect();
+    digest(&bytes)
+}
+
+// 64-bit FNV-1a, stable across builds unlike the std hashers
+fn digest(bytes: &[u8]) -> Felt {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+    Felt::from(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PUBLIC_INPUT: &str = r#"{
+        "layout": "recursive",
+        "n_steps": 16,
+        "memory_segments": {
+            "program": {"begin_addr": 1, "stop_ptr": 3},
+            "output": {"begin_addr": 10, "stop_ptr": 12}
+        },
+        "public_memory": [
+            {"address": 11, "value": "0x7", "page": 0},
+            {"address": 1, "value": "0x40780017fff7fff", "page": 0},
+            {"address": 2, "value": "0x1", "page": 0},
+            {"address": 10, "value": "0x2a", "page": 0}
+        ]
+    }"#;
+
+    #[tokio::test]
+    async fn test_mock_proof_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let paths = ProvePaths::in_dir(dir.path());
+        std::fs::write(&paths.public_input_file, PUBLIC_INPUT).unwrap();
+        let (_tx, mut cancel) = watch::channel(false);
+        let log = MockBackend
+            .prove(&paths, None, ProcessLimits::default(), &mut cancel)
+            .await
+            .unwrap();
+        assert_eq!(log.exit_code, Some(0));
+        assert!(MockBackend.verify(&paths.proof_path).await.unwrap());
+
+        let proof = std::fs::read_to_string(&paths.proof_path).unwrap();
+        let result = MockBackend
+            .extract(proof.clone(), CairoVersion::Cairo)
+            .unwrap();
+        assert_eq!(result.program_output, vec![Felt::from(42), Felt::from(7)]);
+        // The same execution always yields the same proof
+        assert_eq!(
+            result.program_hash,
+            MockBackend
+                .extract(proof, CairoVersion::Cairo)
+                .unwrap()
+                .program_hash
+        );
+
+        let tampered = std::fs::read_to_string(&paths.proof_path)
+            .unwrap()
+            .replace("0x2a", "0x2b");
+        std::fs::write(&paths.proof_path, tampered).unwrap();
+        assert!(!MockBackend.verify(&paths.proof_path).await.unwrap());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    errors::ProverError,
+    threadpool::{limits::ProcessLimits, prove::ProvePaths},
+};
+use common::models::{CairoVersion, CommandLog, ProverResult};
+use common::prover_input::ProverConfig;
+use std::path::Path;
+use tokio::sync::watch;
+
+mod mock;
+mod stone;
+
+pub use mock::MockBackend;
+pub use stone::StoneBackend;
+
+/// Turns an execution trace into a proof, checks proofs and reads results out of them.
+pub(crate) trait ProverBackend {
+    /// Proves the execution the runner wrote to `paths`, leaving the proof at `paths.proof_path`.
+    ///
+    /// `config` holds the job's overrides of the backend's prover config.
+    async fn prove(
+        &self,
+        paths: &ProvePaths,
+        config: Option<&ProverConfig>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError>;
+
+    async fn verify(&self, proof_file: &Path) -> Result<bool, ProverError>;
+
+    /// Extracts the program hash and output from a proof produced by this backend.
+    fn extract(
+        &self,
+        proof: String,
+        cairo_version: CairoVersion,
+    ) -> Result<ProverResult, ProverError>;
+}
+
+/// Selects the backend in `Args`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    #[default]
+    Stone,
+    /// Deterministic fake proofs, for testing without the Stone binaries
+    Mock,
+}
+
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Stone(StoneBackend),
+    Mock(MockBackend),
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::Stone(StoneBackend::default())
+    }
+}
+
+impl Backend {
+    /// `prover_config` is the Stone config file, unused by the mock backend.
+    pub fn new(kind: BackendKind, prover_config: &Path) -> Result<Self, ProverError> {
+        Ok(match kind {
+            BackendKind::Stone => Backend::Stone(StoneBackend::new(prover_config)?),
+            BackendKind::Mock => Backend::Mock(MockBackend),
+        })
+    }
+}
+
+impl ProverBackend for Backend {
+    async fn prove(
+        &self,
+        paths: &ProvePaths,
+        config: Option<&ProverConfig>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError> {
+        match self {
+            Backend::Stone(backend) => backend.prove(paths, config, limits, cancel).await,
+            Backend::Mock(backend) => backend.prove(paths, config, limits, cancel).await,
+        }
+    }
+
+    async fn verify(&self, proof_file: &Path) -> Result<bool, ProverError> {
+        match self {
+            Backend::Stone(backend) => backend.verify(proof_file).await,
+            Backend::Mock(backend) => backend.verify(proof_file).await,
+        }
+    }
+
+    fn extract(
+        &self,
+        proof: String,
+        cairo_version: CairoVersion,
+    ) -> Result<ProverResult, ProverError> {
+        match self {
+            Backend::Stone(backend) => backend.extract(proof, cairo_version),
+            Backend::Mock(backend) => backend.extract(proof, cairo_version),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use super::ProverBackend;
+use crate::{
+    errors::ProverError,
+    threadpool::{limits::ProcessLimits, prove::ProvePaths, run::command_run},
+};
+use cairo_proof_parser::{
+    json_parser::proof_from_annotations, output::ExtractOutputResult,
+    program::ExtractProgramResult, ProofJSON,
+};
+use common::models::{CairoVersion, CommandLog, ProverResult};
+use common::prover_input::{CachedLdeConfig, ProverConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+use tokio::{process::Command, sync::watch};
+
+/// Shells out to the `cpu_air_prover` and `cpu_air_verifier` binaries of Stone.
+#[derive(Debug, Clone, Default)]
+pub struct StoneBackend {
+    config: StoneConfig,
+}
+
+/// The `cpu_air_prover` config, entries jobs can't override are passed through as they are.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct StoneConfig {
+    cached_lde_config: CachedLdeConfig,
+    constraint_polynomial_task_size: u32,
+    n_out_of_memory_merkle_layers: u32,
+    #[serde(flatten)]
+    other: Map<String, Value>,
+}
+
+// The config shipped in `config/cpu_air_prover_config.json`
+impl Default for StoneConfig {
+    fn default() -> Self {
+        Self {
+            cached_lde_config: CachedLdeConfig {
+                store_full_lde: false,
+                use_fft_for_eval: false,
+            },
+            constraint_polynomial_task_size: 256,
+            n_out_of_memory_merkle_layers: 1,
+            other: Map::from_iter([("table_prover_n_tasks_per_segment".to_string(), 32.into())]),
+        }
+    }
+}
+
+impl StoneConfig {
+    fn with_overrides(&self, overrides: &ProverConfig) -> Self {
+        Self {
+            cached_lde_config: overrides
+                .cached_lde_config
+                .unwrap_or(self.cached_lde_config),
+            constraint_polynomial_task_size: overrides
+                .constraint_polynomial_task_size
+                .unwrap_or(self.constraint_polynomial_task_size),
+            n_out_of_memory_merkle_layers: overrides
+                .n_out_of_memory_merkle_layers
+                .unwrap_or(self.n_out_of_memory_merkle_layers),
+            other: self.other.clone(),
+        }
+    }
+}
+
+impl StoneBackend {
+    /// Reads the prover config from `config_file` once, so that a bad path fails at startup.
+    pub fn new(config_file: &Path) -> Result<Self, ProverError> {
+        let config = std::fs::read_to_string(config_file).map_err(|e| {
+            ProverError::CustomError(format!(
+                "Failed to read the prover config {}: {e}",
+                config_file.display()
+            ))
+        })?;
+        Ok(Self {
+            config: serde_json::from_str(&config)?,
+        })
+    }
+
+    fn prove_command(&self, paths: &ProvePaths) -> Command {
+        let mut command = Command::new("cpu_air_prover");
+        command
+            .arg("--out_file")
+            .arg(&paths.proof_path)
+            .arg("--private_input_file")
+            .arg(&paths.private_input_file)
+            .arg("--public_input_file")
+            .arg(&paths.public_input_file)
+            .arg("--prover_config_file")
+            .arg(&paths.config_file)
+            .arg("--parameter_file")
+            .arg(&paths.params_file)
+            .arg("-generate-annotations");
+        command
+    }
+}
+
+impl ProverBackend for StoneBackend {
+    async fn prove(
+        &self,
+        paths: &ProvePaths,
+        config: Option<&ProverConfig>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError> {
+        let config = self
+            .config
+            .with_overrides(&config.cloned().unwrap_or_default());
+        tokio::fs::write(&paths.config_file, serde_json::to_string_pretty(&config)?).await?;
+        command_run(self.prove_command(paths), limits, cancel).await
+    }
+
+    async fn verify(&self, proof_file: &Path) -> Result<bool, ProverError> {
+        let status = Command::new("cpu_air_verifier")
+            .arg("--in_file")
+            .arg(proof_file)
+            .status()
+            .await?;
+        Ok(status.success())
+    }
+
+    fn extract(
+        &self,
+        proof: String,
+        cairo_version: CairoVersion,
+    ) -> Result<ProverResult, ProverError> {
+        let proof_json = serde_json::from_str::<ProofJSON>(&proof)?;
+        let proof_from_annotations = proof_from_annotations(proof_json)?;
+        let ExtractProgramResult { program_hash, .. } = match cairo_version {
+            CairoVersion::Cairo0 => proof_from_annotations
+                .extract_program(cairo_proof_parser::program::CairoVersion::Cairo0)?,
+            CairoVersion::Cairo => proof_from_annotations
+                .extract_program(cairo_proof_parser::program::CairoVersion::Cairo)?,
+        };
+        let ExtractOutputResult {
+            program_output,
+            program_output_hash,
+        } = proof_from_annotations.extract_output()?;
+        let serialized_proof = proof_from_annotations.to_felts();
+        Ok(ProverResult {
+            proof,
+            program_hash,
+            program_output,
+            program_output_hash,
+            serialized_proof,
+            layout: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shipped_config() {
+        let backend = StoneBackend::new(Path::new("../config/cpu_air_prover_config.json")).unwrap();
+        assert_eq!(backend.config, StoneConfig::default());
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let overrides = ProverConfig {
+            n_out_of_memory_merkle_layers: Some(0),
+            cached_lde_config: Some(CachedLdeConfig {
+                store_full_lde: true,
+                use_fft_for_eval: false,
+            }),
+            ..Default::default()
+        };
+        let config =
+            serde_json::to_value(StoneConfig::default().with_overrides(&overrides)).unwrap();
+        assert_eq!(
+            config,
+            serde_json::json!({
+                "cached_lde_config": {"store_full_lde": true, "use_fft_for_eval": false},
+                "constraint_polynomial_task_size": 256,
+                "n_out_of_memory_merkle_layers": 0,
+                "table_prover_n_tasks_per_segment": 32
+            })
+        );
+    }
+}
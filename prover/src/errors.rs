@@ -1,15 +1,18 @@
 use anyhow::Error as AnyhowError;
 use axum::{
-    http::StatusCode,
+    extract::multipart::MultipartError,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use common::models::{FieldError, JobError};
 use serde_json::json;
 use std::{convert::Infallible, net::AddrParseError};
 use thiserror::Error;
 use tokio::sync::mpsc::error::SendError;
 
 use crate::auth::auth_errors::{AuthError, AuthorizerError};
+use crate::utils::config::FriError;
 
 #[derive(Debug, Error)]
 pub enum ProverError {
@@ -33,10 +36,33 @@ pub enum ProverError {
     AddressParse(#[from] AddrParseError),
     #[error(transparent)]
     KeyError(#[from] ed25519_dalek::SignatureError),
-    #[error("Failed to send message via SSE{0}")]
-    SseError(String),
     #[error(transparent)]
     ParserError(#[from] AnyhowError),
+    #[error("Job was cancelled")]
+    Cancelled,
+    #[error("Job queue is full, retry in {0} seconds")]
+    QueueFull(u64),
+    #[error("Thread pool is shutdown")]
+    ShuttingDown,
+    #[error("Program execution failed: {}", .0.message)]
+    RunFailed(JobError),
+    #[error("Invalid submission: {}", join(.0))]
+    InvalidInput(Vec<FieldError>),
+    #[error(transparent)]
+    Fri(#[from] FriError),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error(transparent)]
+    Multipart(#[from] MultipartError),
+    #[error("Upload is larger than {0} bytes")]
+    PayloadTooLarge(usize),
+    #[error("Quota exceeded, {0}")]
+    QuotaExceeded(String),
+}
+
+fn join(errors: &[FieldError]) -> String {
+    let errors: Vec<String> = errors.iter().map(FieldError::to_string).collect();
+    errors.join(", ")
 }
 impl<T> From<SendError<T>> for ProverError {
     fn from(err: SendError<T>) -> ProverError {
@@ -60,6 +86,7 @@ impl IntoResponse for ProverError {
                 AuthError::InvalidToken => (StatusCode::BAD_REQUEST, e.to_string()),
                 AuthError::MissingAuthorizationHeader => (StatusCode::BAD_REQUEST, e.to_string()),
                 AuthError::Unauthorized => (StatusCode::UNAUTHORIZED, e.to_string()),
+                AuthError::Forbidden => (StatusCode::FORBIDDEN, e.to_string()),
             },
             ProverError::InternalServerError(e) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
@@ -83,8 +110,28 @@ impl IntoResponse for ProverError {
             },
             ProverError::AddressParse(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             ProverError::KeyError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-            ProverError::SseError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             ProverError::ParserError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            ProverError::Cancelled => (StatusCode::GONE, self.to_string()),
+            ProverError::QueueFull(retry_after) => {
+                let body = Json(json!({ "error": self.to_string() }));
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    body,
+                )
+                    .into_response();
+            }
+            ProverError::ShuttingDown => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
+            ProverError::RunFailed(_) => (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()),
+            ProverError::Fri(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+            ProverError::NotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            ProverError::Multipart(e) => (e.status(), e.body_text()),
+            ProverError::PayloadTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
+            ProverError::QuotaExceeded(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            ProverError::InvalidInput(errors) => {
+                let body = Json(json!({ "error": self.to_string(), "fields": errors }));
+                return (StatusCode::BAD_REQUEST, body).into_response();
+            }
         };
 
         let body = Json(json!({ "error": error_message }));
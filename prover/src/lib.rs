@@ -1,7 +1,10 @@
 pub mod auth;
+pub mod backend;
 pub mod errors;
 pub mod extractors;
 pub mod prove;
+pub mod run;
+pub mod runner;
 pub mod server;
 pub mod sse;
 pub mod threadpool;
@@ -9,7 +12,10 @@ pub mod utils;
 pub mod verifier;
 use std::path::PathBuf;
 
+use backend::BackendKind;
 use clap::{arg, Parser};
+use runner::RunnerKind;
+use utils::workdir::RetentionPolicy;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -32,4 +38,44 @@ pub struct Args {
     pub num_workers: usize,
     #[arg(long, env, value_delimiter = ',')]
     pub admin_keys: Vec<String>,
+    #[arg(long, env)]
+    pub job_store_path: Option<PathBuf>,
+    #[arg(long, env, default_value = "false")]
+    pub requeue_interrupted_jobs: bool,
+    #[arg(long, env)]
+    pub run_timeout: Option<u64>,
+    #[arg(long, env)]
+    pub prove_timeout: Option<u64>,
+    #[arg(long, env)]
+    pub max_steps: Option<u64>,
+    #[arg(long, env)]
+    pub max_memory_mb: Option<u64>,
+    #[arg(long, env, value_delimiter = ',')]
+    pub key_priorities: Vec<String>,
+    #[arg(long, env, default_value = "100")]
+    pub max_queue_depth: usize,
+    #[arg(long, env, default_value = "30")]
+    pub queue_retry_after: u64,
+    #[arg(long, env, value_enum, default_value = "stone")]
+    pub backend: BackendKind,
+    #[arg(long, env, default_value = "config/cpu_air_prover_config.json")]
+    pub prover_config: PathBuf,
+    #[arg(long, env, value_enum, default_value = "subprocess")]
+    pub runner: RunnerKind,
+    #[arg(long, env)]
+    pub min_security_bits: Option<u32>,
+    #[arg(long, env)]
+    pub proof_cache_max_mb: Option<usize>,
+    #[arg(long, env, default_value = "86400")]
+    pub proof_cache_ttl: u64,
+    #[arg(long, env, value_enum, default_value = "keep-on-failure")]
+    pub workdir_retention: RetentionPolicy,
+    #[arg(long, env, default_value = "1")]
+    pub workdir_retention_hours: u64,
+    #[arg(long, env, default_value = "1024")]
+    pub max_upload_mb: usize,
+    #[arg(long, env, default_value = "100")]
+    pub max_programs_per_key: usize,
+    #[arg(long, env, default_value = "1024")]
+    pub max_program_mb_per_key: u64,
 }
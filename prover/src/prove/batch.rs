@@ -0,0 +1,251 @@
+use super::submit;
+use crate::auth::jwt::Claims;
+use crate::errors::ProverError;
+use crate::server::AppState;
+use crate::threadpool::{
+    prove::ProvePaths, run::prepare_input, CairoVersionedInput, UploadedInput,
+};
+use crate::utils::{
+    cache::{input_digest, program_digest, submission_digest},
+    job::cancel,
+    job_storage::link_or_copy,
+    layout,
+    programs::program_owner,
+    validation::validate,
+};
+use axum::Json;
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use common::models::{CairoVersion, FieldError};
+use common::prover_input::{BatchItem, BatchProgram, BatchProverInput, CompiledProgram};
+use serde_json::Value;
+use starknet_types_core::felt::Felt;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Queues a job for every item of a batch, all of them or none.
+///
+/// Every item is checked before the first job is created, the jobs already created are
+/// cancelled if the queue fills up halfway through. The program is written once and shared
+/// by the work directories of the items rather than copied into every job's input.
+pub async fn root(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    Json(batch): Json<BatchProverInput<Value>>,
+) -> Result<impl IntoResponse, ProverError> {
+    if batch.items.is_empty() {
+        return Err(ProverError::CustomError(
+            "A batch needs at least one item".to_string(),
+        ));
+    }
+    let program = match batch.program {
+        BatchProgram::Program(program) => program,
+        BatchProgram::ProgramHash(hash) => app_state
+            .programs
+            .get(program_owner(&claims, &app_state), &hash)
+            .await?
+            .ok_or_else(|| ProverError::NotFound(format!("Program {hash}")))?,
+    };
+    // The items link the program into their own directories, this one goes with the request
+    let shared = TempDir::new()?;
+    let program = SharedProgram::write(program, shared.path())?;
+    let mut inputs = Vec::with_capacity(batch.items.len());
+    for (index, item) in batch.items.into_iter().enumerate() {
+        let dir = TempDir::new()?;
+        let input = item_input(&program, item, dir.path())
+            .await
+            .and_then(|input| {
+                validate(&input, app_state.min_security_bits)?;
+                Ok(input)
+            })
+            .map_err(|e| item_error(index, e))?;
+        inputs.push((dir, input));
+    }
+    app_state.thread_pool.check_capacity_for(inputs.len())?;
+
+    let mut jobs = Vec::with_capacity(inputs.len());
+    for (dir, program_input) in inputs {
+        match submit(&app_state, claims.clone(), dir, program_input).await {
+            Ok(job_id) => jobs.push(job_id),
+            Err(e) => {
+                // A job that can't be cancelled doesn't keep the others running
+                for job_id in jobs.iter() {
+                    if let Err(cancel_error) = cancel(&app_state, job_id).await {
+                        tracing::warn!(
+                            "Failed to cancel job {} of a rejected batch: {}",
+                            job_id,
+                            cancel_error
+                        );
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+    let batch = app_state.job_store.create_batch(&claims.sub, jobs).await?;
+    let body = Json(app_state.job_store.batch_status(&batch).await);
+    Ok((StatusCode::ACCEPTED, body))
+}
+
+// The program of a batch, in the file the runners read
+struct SharedProgram {
+    cairo_version: CairoVersion,
+    path: PathBuf,
+    builtins: Vec<String>,
+    digest: Vec<u8>,
+}
+
+impl SharedProgram {
+    fn write(program: CompiledProgram, dir: &Path) -> Result<Self, ProverError> {
+        let cairo_version = program.cairo_version();
+        let (contents, builtins) = match program {
+            CompiledProgram::Cairo0(program) => (serde_json::to_vec(&program)?, program.builtins),
+            CompiledProgram::Cairo(program) => (
+                serde_json::to_vec(&program)?,
+                layout::sierra_builtins(&program.type_declarations),
+            ),
+        };
+        let path = dir.join("program.json");
+        std::fs::write(&path, &contents)?;
+        let digest = program_digest(&cairo_version, contents.as_slice())?;
+        Ok(SharedProgram {
+            cairo_version,
+            path,
+            builtins,
+            digest,
+        })
+    }
+}
+
+// Writes the input of an item to `dir`, next to a link to the program
+async fn item_input(
+    program: &SharedProgram,
+    item: BatchItem<Value>,
+    dir: &Path,
+) -> Result<CairoVersionedInput, ProverError> {
+    let paths = ProvePaths::in_dir(dir);
+    let contents = match program.cairo_version {
+        CairoVersion::Cairo0 => serde_json::to_vec(&item.program_input)?,
+        CairoVersion::Cairo => {
+            prepare_input(&serde_json::from_value::<Vec<Felt>>(item.program_input)?).into_bytes()
+        }
+    };
+    tokio::fs::write(&paths.program_input, &contents).await?;
+    link_or_copy(&program.path, &paths.program).await?;
+    Ok(CairoVersionedInput::Upload(UploadedInput {
+        cairo_version: program.cairo_version.clone(),
+        program: paths.program,
+        program_input: paths.program_input,
+        params: item.params,
+        builtins: program.builtins.clone(),
+        digest: submission_digest(
+            &program.digest,
+            &input_digest(&program.cairo_version, contents.as_slice())?,
+        ),
+    }))
+}
+
+// Points the errors of an item at its position in the batch
+fn item_error(index: usize, error: ProverError) -> ProverError {
+    match error {
+        ProverError::InvalidInput(errors) => ProverError::InvalidInput(
+            errors
+                .into_iter()
+                .map(|error| FieldError {
+                    field: format!("items[{index}].{}", error.field),
+                    message: error.message,
+                })
+                .collect(),
+        ),
+        ProverError::Parse(e) => ProverError::CustomError(format!("items[{index}]: {e}")),
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_batch_items() {
+        let batch: BatchProverInput<Value> = serde_json::from_value(json!({
+            "program": {
+                "cairo_version": "Cairo",
+                "program": {
+                    "type_declarations": [],
+                    "libfunc_declarations": [],
+                    "statements": [],
+                    "funcs": [],
+                },
+            },
+            "items": [
+                { "program_input": ["0x1"], "layout": "recursive", "n_queries": 16 },
+                { "program_input": ["0x2"], "layout": "recursive" },
+                { "program_input": [1], "layout": "recursive" },
+            ],
+        }))
+        .unwrap();
+        let BatchProgram::Program(program) = batch.program else {
+            panic!("expected an embedded program");
+        };
+        let shared = TempDir::new().unwrap();
+        let program = SharedProgram::write(program, shared.path()).unwrap();
+        assert_eq!(program.builtins, vec!["output".to_string()]);
+        let mut items = batch.items.into_iter();
+
+        let (first, second) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let CairoVersionedInput::Upload(input) =
+            item_input(&program, items.next().unwrap(), first.path())
+                .await
+                .unwrap()
+        else {
+            panic!("expected an input in files");
+        };
+        assert_eq!(input.params.n_queries, Some(16));
+        assert_eq!(
+            std::fs::read_to_string(&input.program_input).unwrap(),
+            "[1]"
+        );
+        // Every item reads the one program
+        assert_eq!(
+            std::fs::read(&input.program).unwrap(),
+            std::fs::read(&program.path).unwrap()
+        );
+        let CairoVersionedInput::Upload(other) =
+            item_input(&program, items.next().unwrap(), second.path())
+                .await
+                .unwrap()
+        else {
+            panic!("expected an input in files");
+        };
+        assert_ne!(other.digest, input.digest);
+
+        // Felts are hex strings
+        let Err(error) = item_input(&program, items.next().unwrap(), second.path()).await else {
+            panic!("expected an invalid felt");
+        };
+        assert!(matches!(
+            item_error(2, error),
+            ProverError::CustomError(message) if message.starts_with("items[2]")
+        ));
+        let error = ProverError::InvalidInput(vec![FieldError {
+            field: "layout".to_string(),
+            message: "unknown".to_string(),
+        }]);
+        assert!(matches!(
+            item_error(2, error),
+            ProverError::InvalidInput(errors) if errors[0].field == "items[2].layout"
+        ));
+    }
+
+    #[test]
+    fn test_batch_program_hash() {
+        let batch: BatchProverInput<Value> = serde_json::from_value(json!({
+            "program_hash": "0x01",
+            "items": [{ "program_input": {}, "layout": "recursive" }],
+        }))
+        .unwrap();
+        assert_eq!(batch.program, BatchProgram::ProgramHash("0x01".to_string()));
+        assert_eq!(batch.items[0].params.layout, "recursive");
+    }
+}
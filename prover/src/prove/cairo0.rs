@@ -1,34 +1,58 @@
+use super::{submit, upload};
 use crate::auth::jwt::Claims;
+use crate::errors::ProverError;
 use crate::extractors::workdir::TempDirHandle;
 use crate::server::AppState;
-use crate::threadpool::{CairoVersionedInput, ExecuteParams};
+use crate::threadpool::CairoVersionedInput;
+use crate::utils::programs::program_owner;
+use axum::extract::Multipart;
 use axum::Json;
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use common::prover_input::Cairo0ProverInput;
+use common::models::CairoVersion;
+use common::prover_input::{Cairo0CompiledProgram, Cairo0ProverInput, ProgramSubmission};
 use serde_json::json;
+use serde_json::Value;
 
+/// Queues a proof of a program embedded in the submission or referenced by its `program_hash`.
 pub async fn root(
     State(app_state): State<AppState>,
     TempDirHandle(dir): TempDirHandle,
-    _claims: Claims,
-    Json(program_input): Json<Cairo0ProverInput>,
-) -> impl IntoResponse {
-    let thread_pool = app_state.thread_pool.clone();
-    let job_store = app_state.job_store.clone();
-    let job_id = job_store.create_job().await;
-    let thread = thread_pool.lock().await;
-    let execution_params = ExecuteParams {
-        job_id,
-        job_store,
-        dir,
-        program_input: CairoVersionedInput::Cairo0(program_input.clone()),
-        sse_tx: app_state.sse_tx.clone(),
-        n_queries: program_input.clone().n_queries,
-        pow_bits: program_input.pow_bits,
-    };
-    thread.execute(execution_params).await.into_response();
+    claims: Claims,
+    Json(submission): Json<ProgramSubmission<Cairo0CompiledProgram, Value>>,
+) -> Result<impl IntoResponse, ProverError> {
+    let (program, program_input, params) = app_state
+        .programs
+        .resolve(program_owner(&claims, &app_state), submission)
+        .await?;
+    let program_input =
+        CairoVersionedInput::Cairo0(Cairo0ProverInput::new(program, program_input, params));
+    let job_id = submit(&app_state, claims, dir, program_input).await?;
+
+    let body = json!({
+        "job_id": job_id
+    });
+    Ok((StatusCode::ACCEPTED, body.to_string()))
+}
+
+/// Same as [`root`], with the program and its input streamed as a multipart upload.
+pub async fn upload(
+    State(app_state): State<AppState>,
+    TempDirHandle(dir): TempDirHandle,
+    claims: Claims,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, ProverError> {
+    let program_input = upload::read_input(
+        multipart,
+        dir.path(),
+        app_state.max_upload_bytes,
+        CairoVersion::Cairo0,
+    )
+    .await?;
+    let program_input = CairoVersionedInput::Upload(program_input);
+    let job_id = submit(&app_state, claims, dir, program_input).await?;
+
     let body = json!({
         "job_id": job_id
     });
-    (StatusCode::ACCEPTED, body.to_string())
+    Ok((StatusCode::ACCEPTED, body.to_string()))
 }
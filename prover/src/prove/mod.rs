@@ -1,12 +1,221 @@
-use axum::{routing::post, Router};
+use axum::{extract::DefaultBodyLimit, routing::post, Router};
+use common::models::{JobEvent, JobId, ProverResult};
+use tempfile::TempDir;
 
-use crate::server::AppState;
+use crate::{
+    auth::jwt::Claims,
+    errors::ProverError,
+    server::AppState,
+    threadpool::{CairoVersionedInput, ExecuteParams},
+    utils::{
+        cache,
+        job::{NewJob, LEADER_DISCARDED},
+        validation::validate,
+    },
+};
+mod batch;
 mod cairo;
 mod cairo0;
+mod upload;
 
 pub fn router(app_state: AppState) -> Router {
     Router::new()
         .route("/cairo0", post(cairo0::root))
         .route("/cairo", post(cairo::root))
+        // Uploads are capped at `max_upload_bytes` while streaming them
+        .route(
+            "/cairo0/upload",
+            post(cairo0::upload).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/cairo/upload",
+            post(cairo::upload).layer(DefaultBodyLimit::disable()),
+        )
+        // A batch embeds its program once, it may take as much as an upload
+        .route(
+            "/batch",
+            post(batch::root).layer(DefaultBodyLimit::max(app_state.max_upload_bytes)),
+        )
         .with_state(app_state)
 }
+
+/// Creates a job for `program_input` and queues it.
+///
+/// A submission proven before gets a job that is already completed with the cached result, one
+/// identical to a pending or running job gets a job that follows it, raising the execution to its
+/// priority. Otherwise fails with `QueueFull` instead of waiting when the queue has no room,
+/// without leaving a job behind.
+async fn submit(
+    app_state: &AppState,
+    claims: Claims,
+    dir: TempDir,
+    program_input: CairoVersionedInput,
+) -> Result<JobId, ProverError> {
+    validate(&program_input, app_state.min_security_bits)?;
+    let priority = app_state.job_priority(&claims, program_input.priority());
+    let key = cache::key(&program_input)?;
+    if let Some(result) = app_state.proof_cache.get(&key).await {
+        let prover_result: ProverResult = serde_json::from_str(&result)?;
+        let job_id = app_state
+            .job_store
+            .create_completed_job(&claims.sub, priority, &program_input, result)
+            .await?;
+        let event = JobEvent::Completed {
+            result: prover_result,
+        };
+        app_state.events.send([job_id.clone()], event);
+        return Ok(job_id);
+    }
+    let new_job = app_state
+        .job_store
+        .attach_or_create(
+            &key,
+            &claims.sub,
+            priority,
+            &program_input,
+            &app_state.events,
+            || app_state.thread_pool.check_capacity(),
+        )
+        .await?;
+    let job_id = match new_job {
+        NewJob::Attached(job_id) => {
+            // The execution waits at the highest priority of the jobs attached to it
+            let leader = app_state.job_store.leader(&job_id).await;
+            app_state.thread_pool.raise_priority(&leader, priority);
+            return Ok(job_id);
+        }
+        NewJob::Created(job_id) => job_id,
+    };
+    let execution_params = ExecuteParams {
+        job_id: job_id.clone(),
+        owner: claims.sub,
+        priority,
+        job_store: app_state.job_store.clone(),
+        dir,
+        n_queries: program_input.n_queries(),
+        pow_bits: program_input.pow_bits(),
+        program_input,
+        events: app_state.events.clone(),
+        proof_cache: app_state.proof_cache.clone(),
+    };
+    // The queue may have filled up since the check above
+    if let Err(e) = app_state.thread_pool.execute(execution_params) {
+        let failed = app_state.job_store.discard_job(&job_id).await?;
+        let event = JobEvent::Failed {
+            error: e.to_string(),
+            details: None,
+        };
+        app_state.events.send([job_id], event);
+        let event = JobEvent::Failed {
+            error: LEADER_DISCARDED.to_string(),
+            details: None,
+        };
+        app_state.events.send(failed, event);
+        return Err(e);
+    }
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::job::tests::cairo_input;
+    use common::models::JobPriority;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+    use tempfile::tempdir;
+
+    fn claims(sub: &str) -> Claims {
+        Claims {
+            sub: sub.to_string(),
+            exp: 0,
+            session_key: SigningKey::generate(&mut OsRng).verifying_key(),
+        }
+    }
+
+    fn input(priority: JobPriority, program_input: u64) -> CairoVersionedInput {
+        let CairoVersionedInput::Cairo(mut input) = cairo_input() else {
+            unreachable!()
+        };
+        input.priority = Some(priority);
+        input.program_input = vec![program_input.into()];
+        CairoVersionedInput::Cairo(input)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_identical_submissions_share_an_execution() {
+        let app_state = AppState::for_tests().await;
+        let claims = claims("0x01");
+        let (_, start) = app_state.events.subscribe();
+        let submissions = (0..2).map(|_| {
+            let app_state = app_state.clone();
+            let claims = claims.clone();
+            tokio::spawn(async move {
+                submit(&app_state, claims, tempdir().unwrap(), cairo_input()).await
+            })
+        });
+        let mut job_ids = Vec::new();
+        for submission in submissions.collect::<Vec<_>>() {
+            job_ids.push(submission.await.unwrap().unwrap());
+        }
+
+        let queued: Vec<&JobId> = job_ids
+            .iter()
+            .filter(|job_id| app_state.thread_pool.queue_position(job_id).is_some())
+            .collect();
+        assert_eq!(queued.len(), 1);
+        let mut execution = app_state.job_store.execution(queued[0]).await;
+        execution.sort();
+        job_ids.sort();
+        assert_eq!(execution, job_ids);
+
+        // Both are reported queued, the attached job as well
+        let mut reported: Vec<JobId> = app_state
+            .events
+            .since(start, u64::MAX)
+            .unwrap()
+            .iter()
+            .filter(|message| matches!(message.event, JobEvent::Queued))
+            .map(|message| message.job_id.clone())
+            .collect();
+        reported.sort();
+        assert_eq!(reported, job_ids);
+    }
+
+    #[tokio::test]
+    async fn test_attached_job_raises_the_execution() {
+        let mut app_state = AppState::for_tests().await;
+        app_state
+            .key_priorities
+            .insert("0x02".to_string(), JobPriority::High);
+        let dir = || tempdir().unwrap();
+        submit(
+            &app_state,
+            claims("0x03"),
+            dir(),
+            input(JobPriority::Normal, 1),
+        )
+        .await
+        .unwrap();
+        let leader = submit(
+            &app_state,
+            claims("0x01"),
+            dir(),
+            input(JobPriority::Low, 2),
+        )
+        .await
+        .unwrap();
+        assert_eq!(app_state.thread_pool.queue_position(&leader), Some(1));
+
+        let follower = submit(
+            &app_state,
+            claims("0x02"),
+            dir(),
+            input(JobPriority::High, 2),
+        )
+        .await
+        .unwrap();
+        assert_eq!(app_state.job_store.leader(&follower).await, leader);
+        assert_eq!(app_state.thread_pool.queue_position(&leader), Some(0));
+    }
+}
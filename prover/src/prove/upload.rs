@@ -0,0 +1,227 @@
+use axum::extract::Multipart;
+use common::{models::CairoVersion, prover_input::SubmissionParams};
+use serde::{de::IgnoredAny, Deserialize};
+use serde_json::Value;
+use std::{collections::HashSet, fs::File, io::BufReader, path::Path};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    errors::ProverError,
+    threadpool::{prove::ProvePaths, run::prepare_input_file, UploadedInput},
+    utils::{
+        cache::{input_digest, program_digest, submission_digest},
+        layout,
+    },
+};
+
+// Parts holding a JSON file, `params` holds the `SubmissionParams`
+const FILE_PARTS: [&str; 2] = ["program", "program_input"];
+const PARAMS_PART: &str = "params";
+
+// The parts of a compiled program choosing a layout reads, the rest is skipped while parsing
+#[derive(Deserialize)]
+struct Cairo0Builtins {
+    builtins: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SierraTypes {
+    type_declarations: Value,
+}
+
+/// Reads a multipart submission, streaming the program and its input to the files of the job
+/// in `dir`.
+///
+/// The parts together may take at most `max_bytes`. The files stay where the job runs them
+/// from, only `params` and the builtins the program declares are parsed, so the request body
+/// is never held in memory.
+pub(super) async fn read_input(
+    mut multipart: Multipart,
+    dir: &Path,
+    max_bytes: usize,
+    cairo_version: CairoVersion,
+) -> Result<UploadedInput, ProverError> {
+    let paths = ProvePaths::in_dir(dir);
+    // Sierra program input is converted to the form `cairo1-run` reads once uploaded
+    let raw_input = match cairo_version {
+        CairoVersion::Cairo => dir.join("upload_program_input.json"),
+        CairoVersion::Cairo0 => paths.program_input.clone(),
+    };
+    let params_path = dir.join("upload_params.json");
+    let mut parts = HashSet::new();
+    let mut remaining = max_bytes;
+    while let Some(mut field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or_default().to_string();
+        let path = match name.as_str() {
+            "program" => &paths.program,
+            "program_input" => &raw_input,
+            PARAMS_PART => &params_path,
+            _ => {
+                return Err(ProverError::CustomError(format!(
+                    "Unexpected part {name}, expected program, program_input or params"
+                )))
+            }
+        };
+        let mut file = tokio::fs::File::create(path).await?;
+        while let Some(chunk) = field.chunk().await? {
+            remaining = remaining
+                .checked_sub(chunk.len())
+                .ok_or(ProverError::PayloadTooLarge(max_bytes))?;
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        parts.insert(name);
+    }
+    for name in FILE_PARTS.into_iter().chain([PARAMS_PART]) {
+        if !parts.contains(name) {
+            return Err(ProverError::CustomError(format!("Missing part {name}")));
+        }
+    }
+
+    let params: SubmissionParams = serde_json::from_reader(open(&params_path)?)?;
+    std::fs::remove_file(&params_path)?;
+    let builtins = match cairo_version {
+        CairoVersion::Cairo => {
+            let program: SierraTypes = serde_json::from_reader(open(&paths.program)?)?;
+            prepare_input_file(&raw_input, &paths.program_input)?;
+            std::fs::remove_file(&raw_input)?;
+            layout::sierra_builtins(&program.type_declarations)
+        }
+        CairoVersion::Cairo0 => {
+            let program: Cairo0Builtins = serde_json::from_reader(open(&paths.program)?)?;
+            serde_json::from_reader::<_, IgnoredAny>(open(&paths.program_input)?)?;
+            program.builtins
+        }
+    };
+    // Read back from the files, the same submission sent as JSON gets the same digest
+    let digest = submission_digest(
+        &program_digest(&cairo_version, open(&paths.program)?)?,
+        &input_digest(&cairo_version, open(&paths.program_input)?)?,
+    );
+    Ok(UploadedInput {
+        cairo_version,
+        program: paths.program,
+        program_input: paths.program_input,
+        params,
+        builtins,
+        digest,
+    })
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, ProverError> {
+    Ok(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::FromRequest, http::Request};
+    use tempfile::TempDir;
+
+    const BOUNDARY: &str = "upload-boundary";
+
+    fn form(parts: &[(&str, &str)]) -> Request<Body> {
+        let mut body = String::new();
+        for (name, contents) in parts {
+            body.push_str(&format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"; \
+                 filename=\"{name}.json\"\r\n\r\n{contents}\r\n"
+            ));
+        }
+        body.push_str(&format!("--{BOUNDARY}--\r\n"));
+        Request::builder()
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn read(
+        parts: &[(&str, &str)],
+        max_bytes: usize,
+        cairo_version: CairoVersion,
+    ) -> (Result<UploadedInput, ProverError>, TempDir) {
+        let dir = TempDir::new().unwrap();
+        let multipart = Multipart::from_request(form(parts), &()).await.unwrap();
+        let input = read_input(multipart, dir.path(), max_bytes, cairo_version).await;
+        (input, dir)
+    }
+
+    const PROGRAM: &str = r#"{"type_declarations": [], "libfunc_declarations": [],
+        "statements": [], "funcs": []}"#;
+    const FIBONACCI: &str = include_str!("../../../examples/cairo0/fibonacci_compiled.json");
+
+    #[tokio::test]
+    async fn test_read_input() {
+        let parts = [
+            ("program", PROGRAM),
+            ("program_input", r#"["0x1", "0x2"]"#),
+            ("params", r#"{"layout": "recursive", "n_queries": 16}"#),
+        ];
+        let (input, dir) = read(&parts, 1024, CairoVersion::Cairo).await;
+        let input = input.unwrap();
+        assert_eq!(input.params.layout, "recursive");
+        assert_eq!(input.params.n_queries, Some(16));
+        assert!(input.params.pow_bits.is_none());
+        assert_eq!(input.builtins, vec!["output".to_string()]);
+        // The files are where the job runs them from, nothing else is left behind
+        let paths = ProvePaths::in_dir(dir.path());
+        assert_eq!(input.program, paths.program);
+        assert_eq!(std::fs::read_to_string(&input.program).unwrap(), PROGRAM);
+        assert_eq!(
+            std::fs::read_to_string(&input.program_input).unwrap(),
+            "[1 2]"
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        // Identical uploads share a digest
+        let (again, _dir) = read(&parts, 1024, CairoVersion::Cairo).await;
+        assert_eq!(again.unwrap().digest, input.digest);
+        let parts = [parts[0], ("program_input", r#"["0x1"]"#), parts[2]];
+        let (other, _dir) = read(&parts, 1024, CairoVersion::Cairo).await;
+        assert_ne!(other.unwrap().digest, input.digest);
+    }
+
+    #[tokio::test]
+    async fn test_read_cairo0_input() {
+        let parts = [
+            ("program", FIBONACCI),
+            ("program_input", r#"{"fibonacci_claim_index": 10}"#),
+            ("params", r#"{"layout": "auto"}"#),
+        ];
+        let (input, _dir) = read(&parts, FIBONACCI.len() + 1024, CairoVersion::Cairo0).await;
+        let input = input.unwrap();
+        assert_eq!(
+            input.builtins,
+            ["output", "pedersen", "range_check", "bitwise"].map(String::from)
+        );
+        assert_eq!(
+            std::fs::read_to_string(&input.program_input).unwrap(),
+            parts[1].1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_limit() {
+        let (result, _dir) = read(&[("program", PROGRAM)], 16, CairoVersion::Cairo).await;
+        assert!(matches!(result, Err(ProverError::PayloadTooLarge(16))));
+
+        let parts = [("program", PROGRAM), ("program_input", r#"["0x1"]"#)];
+        let (result, _dir) = read(&parts, 1024, CairoVersion::Cairo).await;
+        assert!(matches!(
+            result,
+            Err(ProverError::CustomError(message)) if message == "Missing part params"
+        ));
+
+        // Felts are hex strings
+        let parts = [
+            ("program", PROGRAM),
+            ("program_input", "[1]"),
+            ("params", r#"{"layout": "recursive"}"#),
+        ];
+        let (result, _dir) = read(&parts, 1024, CairoVersion::Cairo).await;
+        assert!(matches!(result, Err(ProverError::Parse(_))));
+    }
+}
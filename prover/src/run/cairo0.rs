@@ -0,0 +1,32 @@
+use crate::auth::jwt::Claims;
+use crate::errors::ProverError;
+use crate::extractors::workdir::TempDirHandle;
+use crate::server::AppState;
+use crate::threadpool::CairoVersionedInput;
+use crate::utils::programs::program_owner;
+use crate::utils::validation::validate;
+use axum::extract::State;
+use axum::Json;
+use common::models::RunResult;
+use common::prover_input::{Cairo0CompiledProgram, Cairo0ProverInput, ProgramSubmission};
+use serde_json::Value;
+
+pub async fn root(
+    State(app_state): State<AppState>,
+    TempDirHandle(dir): TempDirHandle,
+    claims: Claims,
+    Json(submission): Json<ProgramSubmission<Cairo0CompiledProgram, Value>>,
+) -> Result<Json<RunResult>, ProverError> {
+    let (program, program_input, params) = app_state
+        .programs
+        .resolve(program_owner(&claims, &app_state), submission)
+        .await?;
+    let program_input =
+        CairoVersionedInput::Cairo0(Cairo0ProverInput::new(program, program_input, params));
+    validate(&program_input, app_state.min_security_bits)?;
+    let result = app_state
+        .thread_pool
+        .run_only(&program_input, dir.path())
+        .await?;
+    Ok(Json(result))
+}
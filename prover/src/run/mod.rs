@@ -0,0 +1,14 @@
+use axum::{routing::post, Router};
+
+use crate::server::AppState;
+mod cairo;
+mod cairo0;
+
+/// Execute-only counterparts of the `/prove` routes, they respond with the `RunResult` once the
+/// program ran instead of queueing a job.
+pub fn router(app_state: AppState) -> Router {
+    Router::new()
+        .route("/cairo0", post(cairo0::root))
+        .route("/cairo", post(cairo::root))
+        .with_state(app_state)
+}
@@ -0,0 +1,72 @@
+use crate::{
+    errors::ProverError,
+    threadpool::{limits::ProcessLimits, run::RunPaths, CairoVersionedInput},
+};
+use common::models::CommandLog;
+use tokio::sync::watch;
+
+mod subprocess;
+mod vm;
+
+pub use subprocess::SubprocessRunner;
+pub use vm::VmRunner;
+
+/// Executes a program in proof mode, producing the files the prover backend works on.
+pub(crate) trait CairoRunner {
+    /// Runs `input`, writing the trace, memory and AIR public and private inputs to `paths`.
+    ///
+    /// A failing program is reported through the returned log, not as an error.
+    async fn run(
+        &self,
+        input: &CairoVersionedInput,
+        paths: &RunPaths<'_>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError>;
+}
+
+/// Selects the runner in `Args`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunnerKind {
+    /// `cairo1-run` and the Python `cairo-run`
+    #[default]
+    Subprocess,
+    /// cairo-vm inside the server process
+    Vm,
+}
+
+#[derive(Debug, Clone)]
+pub enum Runner {
+    Subprocess(SubprocessRunner),
+    Vm(VmRunner),
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Runner::new(RunnerKind::default())
+    }
+}
+
+impl Runner {
+    pub fn new(kind: RunnerKind) -> Self {
+        match kind {
+            RunnerKind::Subprocess => Runner::Subprocess(SubprocessRunner),
+            RunnerKind::Vm => Runner::Vm(VmRunner),
+        }
+    }
+}
+
+impl CairoRunner for Runner {
+    async fn run(
+        &self,
+        input: &CairoVersionedInput,
+        paths: &RunPaths<'_>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError> {
+        match self {
+            Runner::Subprocess(runner) => runner.run(input, paths, limits, cancel).await,
+            Runner::Vm(runner) => runner.run(input, paths, limits, cancel).await,
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use super::CairoRunner;
+use crate::{
+    errors::ProverError,
+    threadpool::{
+        limits::ProcessLimits,
+        run::{command_run, RunPaths},
+        CairoVersionedInput,
+    },
+};
+use common::models::{CairoVersion, CommandLog};
+use tokio::{process::Command, sync::watch};
+use tracing::trace;
+
+/// Runs programs with `cairo1-run` and the Python `cairo-run`, which need to be installed.
+#[derive(Debug, Clone, Copy)]
+pub struct SubprocessRunner;
+
+impl SubprocessRunner {
+    fn cairo1_run_command(paths: &RunPaths<'_>, layout: &str) -> Command {
+        let mut command = Command::new("cairo1-run");
+        command
+            .arg("--trace_file")
+            .arg(paths.trace_file)
+            .arg("--memory_file")
+            .arg(paths.memory_file)
+            .arg("--layout")
+            .arg(layout)
+            .arg("--proof_mode")
+            .arg("--air_public_input")
+            .arg(paths.public_input_file)
+            .arg("--air_private_input")
+            .arg(paths.private_input_file)
+            .arg("--args_file")
+            .arg(paths.program_input_path)
+            .arg(paths.program);
+        command
+    }
+    fn cairo0_run_command(paths: &RunPaths<'_>, layout: &str) -> Command {
+        let mut command = Command::new("cairo-run");
+        command
+            .arg("--trace_file")
+            .arg(paths.trace_file)
+            .arg("--memory_file")
+            .arg(paths.memory_file)
+            .arg("--layout")
+            .arg(layout)
+            .arg("--proof_mode")
+            .arg("--air_public_input")
+            .arg(paths.public_input_file)
+            .arg("--air_private_input")
+            .arg(paths.private_input_file)
+            .arg("--program_input")
+            .arg(paths.program_input_path)
+            .arg("--program")
+            .arg(paths.program);
+        command
+    }
+}
+
+impl CairoRunner for SubprocessRunner {
+    async fn run(
+        &self,
+        input: &CairoVersionedInput,
+        paths: &RunPaths<'_>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError> {
+        match input.cairo_version() {
+            CairoVersion::Cairo => {
+                trace!("Running cairo1-run");
+                let command = Self::cairo1_run_command(paths, input.layout());
+                command_run(command, limits, cancel).await
+            }
+            CairoVersion::Cairo0 => {
+                trace!("Running cairo0-run");
+                let command = Self::cairo0_run_command(paths, input.layout());
+                command_run(command, limits, cancel).await
+            }
+        }
+    }
+}
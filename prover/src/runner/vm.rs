@@ -0,0 +1,467 @@
+use super::{CairoRunner, SubprocessRunner};
+use crate::{
+    errors::ProverError,
+    threadpool::{limits::ProcessLimits, run::RunPaths, CairoVersionedInput},
+};
+use bincode::{enc::write::Writer, error::EncodeError};
+use cairo_vm::{
+    cairo_run::{cairo_run, write_encoded_memory, write_encoded_trace, CairoRunConfig},
+    hint_processor::{
+        builtin_hint_processor::{
+            builtin_hint_processor_definition::{BuiltinHintProcessor, HintFunc},
+            hint_utils::insert_value_from_var_name,
+        },
+        hint_processor_definition::{HintProcessorLogic, HintReference},
+    },
+    serde::deserialize_program::ApTracking,
+    types::{exec_scope::ExecutionScopes, layout_name::LayoutName},
+    vm::{
+        errors::{hint_errors::HintError, vm_errors::VirtualMachineError},
+        runners::cairo_runner::{ResourceTracker, RunResources},
+        vm_core::VirtualMachine,
+    },
+    Felt252,
+};
+use clap::ValueEnum;
+use common::models::{CairoVersion, CommandLog};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    any::Any,
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::{sync::watch, task::spawn_blocking};
+
+/// Runs Cairo 0 programs with cairo-vm inside the server, without the Python toolchain.
+///
+/// Only hints implemented by cairo-vm are supported, along with hints assigning entries of the
+/// program input to ids like `ids.n = program_input['n']`. Programs reading `program_input` in
+/// any other Python hint need the subprocess runner. Sierra programs are still run with
+/// `cairo1-run`.
+#[derive(Debug, Clone, Copy)]
+pub struct VmRunner;
+
+// Owned copies of the output paths, the VM runs on a blocking thread
+struct VmFiles {
+    trace_file: PathBuf,
+    memory_file: PathBuf,
+    public_input_file: PathBuf,
+    private_input_file: PathBuf,
+}
+
+impl CairoRunner for VmRunner {
+    async fn run(
+        &self,
+        input: &CairoVersionedInput,
+        paths: &RunPaths<'_>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError> {
+        if input.cairo_version() != CairoVersion::Cairo0 {
+            return SubprocessRunner.run(input, paths, limits, cancel).await;
+        }
+        // Read from the prepared files, uploaded programs are never parsed in full otherwise
+        let program = tokio::fs::read(paths.program).await?;
+        let program_input = tokio::fs::read(paths.program_input_path).await?;
+        let layout = input.layout().to_string();
+        let max_steps = limits
+            .max_steps
+            .map(|max_steps| usize::try_from(max_steps).unwrap_or(usize::MAX));
+        let files = VmFiles {
+            trace_file: paths.trace_file.clone(),
+            memory_file: paths.memory_file.clone(),
+            public_input_file: paths.public_input_file.clone(),
+            private_input_file: paths.private_input_file.clone(),
+        };
+        // Stops the VM at its next step on a timeout or cancellation, the memory limit only
+        // applies to subprocesses
+        let stop = Arc::new(AtomicBool::new(false));
+        let vm_stop = stop.clone();
+        let run = spawn_blocking(move || {
+            let hints = program_hints(&program, &program_input)?;
+            let mut hint_processor = VmHintProcessor::new(hints, max_steps, vm_stop);
+            run_in_vm(&program, &layout, &files, &mut hint_processor)
+        });
+        let (result, killed) = tokio::select! {
+            result = run => {
+                let result = result.map_err(|e| ProverError::InternalServerError(e.to_string()))?;
+                (result, None)
+            }
+            reason = limits.exceeded(None) => {
+                stop.store(true, Ordering::Relaxed);
+                (Ok(String::new()), Some(reason))
+            }
+            Ok(_) = cancel.wait_for(|cancelled| *cancelled) => {
+                stop.store(true, Ordering::Relaxed);
+                return Err(ProverError::Cancelled);
+            }
+        };
+        let (exit_code, stdout, stderr) = match (killed, result) {
+            (Some(_), _) => (None, String::new(), String::new()),
+            (None, Ok(output)) => (Some(0), output, String::new()),
+            (None, Err(error)) => (Some(1), String::new(), error),
+        };
+        Ok(CommandLog {
+            command: "cairo-vm".to_string(),
+            exit_code,
+            stdout,
+            stderr,
+            truncated: false,
+            killed,
+        })
+    }
+}
+
+// Assignments of program input entries to ids, by the code of the hint making them
+type InputHints = HashMap<String, Vec<(String, Felt252)>>;
+
+// The hints of a compiled program, the rest of it is skipped while parsing
+#[derive(Deserialize)]
+struct ProgramHints {
+    #[serde(default)]
+    hints: Value,
+}
+
+fn program_hints(program: &[u8], program_input: &[u8]) -> Result<InputHints, String> {
+    let ProgramHints { hints } = serde_json::from_slice(program)
+        .map_err(|e| format!("The program is not a compiled Cairo 0 program: {e}"))?;
+    let program_input = serde_json::from_slice(program_input)
+        .map_err(|e| format!("The program input is not valid JSON: {e}"))?;
+    input_hints(&hints, &program_input)
+}
+
+// Collects the hints reading `program_input`, which all have to be plain assignments to ids
+fn input_hints(hints: &Value, program_input: &Value) -> Result<InputHints, String> {
+    let codes = hints
+        .as_object()
+        .into_iter()
+        .flat_map(|hints| hints.values())
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(|hint| hint.get("code")?.as_str());
+    let mut input_hints = HashMap::new();
+    for code in codes.filter(|code| code.contains("program_input")) {
+        let assignments = code
+            .lines()
+            .map(|line| input_assignment(line.trim(), program_input))
+            .collect::<Result<_, _>>()?;
+        input_hints.insert(code.to_string(), assignments);
+    }
+    Ok(input_hints)
+}
+
+// Parses `ids.<name> = program_input['<key>']`, looking the value up in `program_input`
+fn input_assignment(line: &str, program_input: &Value) -> Result<(String, Felt252), String> {
+    let unsupported = || {
+        format!(
+            "The vm runner can't run the hint `{line}`, only hints like \
+             `ids.n = program_input['n']` read the program input. Use the subprocess runner"
+        )
+    };
+    let (name, value) = line.split_once('=').ok_or_else(unsupported)?;
+    let name = name.trim().strip_prefix("ids.").ok_or_else(unsupported)?;
+    let key = value
+        .trim()
+        .strip_prefix("program_input[")
+        .and_then(|key| key.strip_suffix(']'))
+        .and_then(|key| {
+            let key = key.strip_prefix('\'').or_else(|| key.strip_prefix('"'))?;
+            key.strip_suffix('\'').or_else(|| key.strip_suffix('"'))
+        })
+        .ok_or_else(unsupported)?;
+    let invalid = || format!("program_input['{key}'] must be an integer");
+    let value = match program_input.get(key) {
+        Some(Value::Number(number)) => match (number.as_u64(), number.as_i64()) {
+            (Some(value), _) => Felt252::from(value),
+            (None, Some(value)) => Felt252::from(value),
+            _ => return Err(invalid()),
+        },
+        Some(Value::String(value)) if value.starts_with("0x") => {
+            Felt252::from_hex(value).map_err(|_| invalid())?
+        }
+        Some(Value::String(value)) => Felt252::from_dec_str(value).map_err(|_| invalid())?,
+        Some(_) => return Err(invalid()),
+        None => return Err(format!("program_input has no entry {key}")),
+    };
+    Ok((name.to_string(), value))
+}
+
+// Runs the builtin hints and the program input assignments, stopping after `max_steps` steps
+// or once `stop` is set
+struct VmHintProcessor {
+    hints: BuiltinHintProcessor,
+    steps: RunResources,
+    stop: Arc<AtomicBool>,
+}
+
+impl VmHintProcessor {
+    fn new(input_hints: InputHints, max_steps: Option<usize>, stop: Arc<AtomicBool>) -> Self {
+        let mut hints = BuiltinHintProcessor::new_empty();
+        for (code, assignments) in input_hints {
+            let assign = move |vm: &mut VirtualMachine,
+                               _: &mut ExecutionScopes,
+                               ids_data: &HashMap<String, HintReference>,
+                               ap_tracking: &ApTracking,
+                               _: &HashMap<String, Felt252>| {
+                for (name, value) in &assignments {
+                    insert_value_from_var_name(name, *value, vm, ids_data, ap_tracking)?;
+                }
+                Ok(())
+            };
+            hints.add_hint(code, Rc::new(HintFunc(Box::new(assign))));
+        }
+        Self {
+            hints,
+            steps: max_steps.map(RunResources::new).unwrap_or_default(),
+            stop,
+        }
+    }
+    fn steps_exceeded(&self) -> bool {
+        self.steps.consumed()
+    }
+}
+
+impl HintProcessorLogic for VmHintProcessor {
+    fn execute_hint(
+        &mut self,
+        vm: &mut VirtualMachine,
+        exec_scopes: &mut ExecutionScopes,
+        hint_data: &Box<dyn Any>,
+        constants: &HashMap<String, Felt252>,
+    ) -> Result<(), HintError> {
+        self.hints
+            .execute_hint(vm, exec_scopes, hint_data, constants)
+    }
+    fn compile_hint(
+        &self,
+        hint_code: &str,
+        ap_tracking_data: &ApTracking,
+        reference_ids: &HashMap<String, usize>,
+        references: &[HintReference],
+    ) -> Result<Box<dyn Any>, VirtualMachineError> {
+        self.hints
+            .compile_hint(hint_code, ap_tracking_data, reference_ids, references)
+    }
+}
+
+impl ResourceTracker for VmHintProcessor {
+    fn consumed(&self) -> bool {
+        self.steps.consumed() || self.stop.load(Ordering::Relaxed)
+    }
+    fn consume_step(&mut self) {
+        self.steps.consume_step()
+    }
+    fn get_n_steps(&self) -> Option<usize> {
+        self.steps.get_n_steps()
+    }
+    fn run_resources(&self) -> &RunResources {
+        &self.steps
+    }
+}
+
+// Returns the program output, or why the program couldn't be run
+fn run_in_vm(
+    program: &[u8],
+    layout: &str,
+    files: &VmFiles,
+    hint_processor: &mut VmHintProcessor,
+) -> Result<String, String> {
+    let config = CairoRunConfig {
+        trace_enabled: true,
+        relocate_mem: true,
+        layout: LayoutName::from_str(layout, false)?,
+        proof_mode: true,
+        ..Default::default()
+    };
+    let mut runner = cairo_run(program, &config, hint_processor).map_err(|e| {
+        if hint_processor.steps_exceeded() {
+            "The execution exceeded its step limit".to_string()
+        } else {
+            e.to_string()
+        }
+    })?;
+
+    let mut output = String::new();
+    runner
+        .vm
+        .write_output(&mut output)
+        .map_err(|e| e.to_string())?;
+
+    let trace = runner
+        .relocated_trace
+        .as_ref()
+        .ok_or("The VM didn't record a trace")?;
+    let mut writer = FileWriter::create(&files.trace_file)?;
+    write_encoded_trace(trace, &mut writer).map_err(|e| e.to_string())?;
+    writer.flush()?;
+
+    let mut writer = FileWriter::create(&files.memory_file)?;
+    write_encoded_memory(&runner.relocated_memory, &mut writer).map_err(|e| e.to_string())?;
+    writer.flush()?;
+
+    let public_input = runner
+        .get_air_public_input()
+        .and_then(|public_input| public_input.serialize_json())
+        .map_err(|e| e.to_string())?;
+    std::fs::write(&files.public_input_file, public_input).map_err(|e| e.to_string())?;
+
+    let private_input = runner
+        .get_air_private_input()
+        .to_serializable(
+            files.trace_file.to_string_lossy().into_owned(),
+            files.memory_file.to_string_lossy().into_owned(),
+        )
+        .serialize_json()
+        .map_err(|e| e.to_string())?;
+    std::fs::write(&files.private_input_file, private_input).map_err(|e| e.to_string())?;
+
+    Ok(output)
+}
+
+// Lets cairo-vm encode the trace and memory straight into a file
+struct FileWriter(BufWriter<File>);
+
+impl FileWriter {
+    fn create(path: &PathBuf) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        Ok(Self(BufWriter::new(file)))
+    }
+    fn flush(&mut self) -> Result<(), String> {
+        self.0.flush().map_err(|e| e.to_string())
+    }
+}
+
+impl Writer for FileWriter {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        self.0
+            .write_all(bytes)
+            .map_err(|inner| EncodeError::Io { inner, index: 0 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::prover_input::{Cairo0CompiledProgram, Cairo0ProverInput};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    const FIBONACCI: &str = include_str!("../../../examples/cairo0/fibonacci_compiled.json");
+
+    // Runs the fibonacci example in a new directory, which holds the files the run wrote
+    async fn run(
+        program_input: Value,
+        layout: &str,
+        max_steps: Option<u64>,
+    ) -> (CommandLog, TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        let (trace, memory, public_input, private_input, input_path, program) = (
+            path("trace"),
+            path("memory"),
+            path("public_input.json"),
+            path("private_input.json"),
+            path("input.json"),
+            path("program.json"),
+        );
+        let input = CairoVersionedInput::Cairo0(Cairo0ProverInput {
+            program: serde_json::from_str::<Cairo0CompiledProgram>(FIBONACCI).unwrap(),
+            program_input,
+            layout: layout.to_string(),
+            n_queries: None,
+            pow_bits: None,
+            priority: None,
+            prover_params: None,
+            prover_config: None,
+        });
+        let paths = RunPaths {
+            trace_file: &trace,
+            memory_file: &memory,
+            public_input_file: &public_input,
+            private_input_file: &private_input,
+            program_input_path: &input_path,
+            program: &program,
+        };
+        input.prepare(&paths).unwrap();
+        let limits = ProcessLimits {
+            timeout: Some(Duration::from_secs(60)),
+            max_memory: None,
+            max_steps,
+        };
+        let (_tx, mut cancel) = watch::channel(false);
+
+        let log = VmRunner
+            .run(&input, &paths, limits, &mut cancel)
+            .await
+            .unwrap();
+        (log, dir)
+    }
+
+    #[tokio::test]
+    async fn test_run_writes_prover_inputs() {
+        let input = serde_json::json!({ "fibonacci_claim_index": 10 });
+        let (log, dir) = run(input, "recursive", None).await;
+        assert_eq!(log.exit_code, Some(0), "{}", log.stderr);
+        // The claimed index and the fibonacci number it leads to
+        assert_eq!(log.stdout, "10\n144\n");
+        for file in ["trace", "memory", "public_input.json", "private_input.json"] {
+            let size = std::fs::metadata(dir.path().join(file)).unwrap().len();
+            assert!(size > 0, "{file} is empty");
+        }
+        let public_input: Value =
+            serde_json::from_slice(&std::fs::read(dir.path().join("public_input.json")).unwrap())
+                .unwrap();
+        assert_eq!(public_input["layout"], "recursive");
+    }
+
+    #[tokio::test]
+    async fn test_failed_run_is_logged() {
+        let input = serde_json::json!({ "fibonacci_claim_index": 10 });
+        let (log, _dir) = run(input, "no_such_layout", None).await;
+        assert_eq!(log.exit_code, Some(1));
+        assert!(log.stderr.contains("no_such_layout"));
+
+        let (log, _dir) = run(serde_json::json!({}), "recursive", None).await;
+        assert_eq!(log.exit_code, Some(1));
+        assert!(log.stderr.contains("fibonacci_claim_index"));
+    }
+
+    #[tokio::test]
+    async fn test_step_limit() {
+        let input = serde_json::json!({ "fibonacci_claim_index": 10 });
+        let (log, _dir) = run(input, "recursive", Some(10)).await;
+        assert_eq!(log.exit_code, Some(1));
+        assert!(log.stderr.contains("step limit"));
+    }
+
+    #[test]
+    fn test_input_hints() {
+        let hints = serde_json::json!({
+            "0": [{ "code": "ids.a = program_input['a']\nids.b = program_input[\"b\"]" }],
+            "4": [{ "code": "memory[ap] = 1" }],
+        });
+        let input = serde_json::json!({ "a": 1, "b": "0x2" });
+        let hints_by_code = input_hints(&hints, &input).unwrap();
+        assert_eq!(hints_by_code.len(), 1);
+        let assignments = hints_by_code.values().next().unwrap();
+        assert_eq!(
+            assignments,
+            &vec![
+                ("a".to_string(), Felt252::from(1)),
+                ("b".to_string(), Felt252::from(2))
+            ]
+        );
+
+        let hints = serde_json::json!({ "0": [{ "code": "print(program_input)" }] });
+        let error = input_hints(&hints, &input).unwrap_err();
+        assert!(error.contains("subprocess runner"));
+    }
+}
@@ -1,25 +1,38 @@
 use crate::auth::auth;
 use crate::auth::auth_errors::AuthorizerError;
 use crate::auth::authorizer::{AuthorizationProvider, Authorizer, FileAuthorizer};
+use crate::auth::jwt::Claims;
+use crate::backend::Backend;
 use crate::errors::ProverError;
 use crate::extractors::workdir::TempDirHandle;
+use crate::runner::Runner;
 use crate::sse::sse_handler;
-use crate::threadpool::ThreadPool;
-use crate::utils::job::{get_job, JobStore};
+use crate::threadpool::{ExecuteParams, JobLimits, ThreadPool};
+use crate::utils::cache::{purge_cache, ProofCache};
+use crate::utils::events::JobEvents;
+use crate::utils::job::{cancel_job, get_batch, get_job, get_job_logs, list_jobs, JobStore};
+use crate::utils::job_storage::{FileJobStorage, JobStorage};
+use crate::utils::programs::{
+    delete_program, list_programs, register_program, ProgramQuota, ProgramRegistry,
+};
 use crate::utils::shutdown::shutdown_signal;
+use crate::utils::workdir::{get_artifact, janitor, Retention};
 use crate::verifier::verify_proof;
-use crate::{prove, Args};
+use crate::{prove, run, Args};
 use axum::{
+    extract::DefaultBodyLimit,
     middleware,
-    routing::{get, post},
+    routing::{delete, get, post},
     serve, Router,
 };
+use common::models::{JobEvent, JobPriority};
 use core::net::SocketAddr;
 use ed25519_dalek::VerifyingKey;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
 use tokio::net::TcpListener;
-use tokio::sync::broadcast::{self, Sender};
 use tokio::sync::Mutex;
 use tracing::trace;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -28,14 +41,65 @@ type NonceString = String;
 #[derive(Clone)]
 pub struct AppState {
     pub job_store: JobStore,
-    pub thread_pool: Arc<Mutex<ThreadPool>>,
+    pub thread_pool: Arc<ThreadPool>,
     pub message_expiration_time: usize,
     pub session_expiration_time: usize,
     pub jwt_secret_key: String,
     pub nonces: Arc<Mutex<HashMap<NonceString, VerifyingKey>>>,
     pub authorizer: Authorizer,
     pub admin_keys: Vec<VerifyingKey>,
-    pub sse_tx: Arc<Mutex<Sender<String>>>,
+    /// Highest priority each hex encoded public key may request, `Normal` for unlisted keys
+    pub key_priorities: HashMap<String, JobPriority>,
+    pub events: JobEvents,
+    pub backend: Backend,
+    /// Submissions requesting proofs with fewer bits of security are rejected
+    pub min_security_bits: Option<u32>,
+    pub proof_cache: ProofCache,
+    /// Largest multipart submission accepted, in bytes
+    pub max_upload_bytes: usize,
+    pub programs: ProgramRegistry,
+}
+
+impl AppState {
+    /// Priority a job gets scheduled with, the requested one capped at what the key may use.
+    ///
+    /// Admin keys may use every class, other keys default to their highest allowed class.
+    pub fn job_priority(&self, claims: &Claims, requested: Option<JobPriority>) -> JobPriority {
+        let allowed = if claims.is_admin(&self.admin_keys) {
+            JobPriority::High
+        } else {
+            self.key_priorities
+                .get(&claims.sub)
+                .copied()
+                .unwrap_or_default()
+        };
+        requested.map_or(allowed, |requested| requested.min(allowed))
+    }
+
+    /// An open server proving with the mock backend, its pool has no workers so that queued
+    /// jobs stay in the queue for tests to inspect.
+    #[cfg(test)]
+    pub(crate) async fn for_tests() -> Self {
+        AppState {
+            jwt_secret_key: "secret".to_string(),
+            job_store: Default::default(),
+            message_expiration_time: 100,
+            session_expiration_time: 100,
+            thread_pool: Arc::new(ThreadPool::without_workers(100)),
+            nonces: Arc::new(Mutex::new(HashMap::new())),
+            authorizer: Authorizer::Open,
+            key_priorities: HashMap::new(),
+            admin_keys: vec![],
+            events: JobEvents::default(),
+            backend: Backend::Mock(crate::backend::MockBackend),
+            min_security_bits: None,
+            proof_cache: ProofCache::default(),
+            max_upload_bytes: 0,
+            programs: ProgramRegistry::new(JobStorage::Memory, ProgramQuota::default())
+                .await
+                .unwrap(),
+        }
+    }
 }
 
 pub async fn start(args: Args) -> Result<(), ProverError> {
@@ -64,19 +128,92 @@ pub async fn start(args: Args) -> Result<(), ProverError> {
         let verifying_key = VerifyingKey::from_bytes(&verifying_key_bytes.try_into()?)?;
         authorizer.authorize(verifying_key).await?;
     }
-    let (sse_tx, _) = broadcast::channel(100);
+    let mut key_priorities = HashMap::new();
+    for entry in args.key_priorities.iter() {
+        let (key, priority) = entry.split_once('=').ok_or_else(|| {
+            ProverError::CustomError(format!(
+                "Invalid key priority {entry}, expected KEY=PRIORITY"
+            ))
+        })?;
+        let verifying_key_bytes = prefix_hex::decode::<Vec<u8>>(key)
+            .map_err(|e| AuthorizerError::PrefixHexConversionError(e.to_string()))?;
+        let verifying_key = VerifyingKey::from_bytes(&verifying_key_bytes.try_into()?)?;
+        let priority = priority.parse().map_err(ProverError::CustomError)?;
+        // Keyed the same way as `Claims::sub`
+        key_priorities.insert(prefix_hex::encode(verifying_key.to_bytes()), priority);
+    }
+    let job_storage = match args.job_store_path {
+        Some(path) => JobStorage::Persistent(FileJobStorage::new(path).await?),
+        None => JobStorage::Memory,
+    };
+    let limits = JobLimits {
+        run_timeout: args.run_timeout.map(Duration::from_secs),
+        prove_timeout: args.prove_timeout.map(Duration::from_secs),
+        max_steps: args.max_steps,
+        max_memory: args.max_memory_mb.map(|mb| mb * 1024 * 1024),
+    };
+    let backend = Backend::new(args.backend, &args.prover_config)?;
     let app_state = AppState {
         message_expiration_time: args.message_expiration_time,
         session_expiration_time: args.session_expiration_time,
         jwt_secret_key: args.jwt_secret_key,
         nonces: Arc::new(Mutex::new(HashMap::new())),
         authorizer,
-        job_store: JobStore::default(),
-        thread_pool: Arc::new(Mutex::new(ThreadPool::new(args.num_workers))),
+        programs: ProgramRegistry::new(
+            job_storage.clone(),
+            ProgramQuota {
+                max_programs: args.max_programs_per_key,
+                max_bytes: args.max_program_mb_per_key * 1024 * 1024,
+            },
+        )
+        .await?,
+        job_store: JobStore::new(job_storage).await?.with_retention(Retention {
+            policy: args.workdir_retention,
+            keep_for: Duration::from_secs(args.workdir_retention_hours * 60 * 60),
+        }),
+        thread_pool: Arc::new(ThreadPool::new(
+            args.num_workers,
+            args.max_queue_depth,
+            args.queue_retry_after,
+            limits,
+            backend.clone(),
+            Runner::new(args.runner),
+        )),
         admin_keys,
-        sse_tx: Arc::new(Mutex::new(sse_tx)),
+        key_priorities,
+        events: JobEvents::default(),
+        backend,
+        min_security_bits: args.min_security_bits,
+        proof_cache: ProofCache::new(
+            args.proof_cache_max_mb.unwrap_or(0) * 1024 * 1024,
+            Duration::from_secs(args.proof_cache_ttl),
+        ),
+        max_upload_bytes: args.max_upload_mb * 1024 * 1024,
     };
 
+    let recovered = app_state
+        .job_store
+        .recover(args.requeue_interrupted_jobs)
+        .await?;
+    for (job, program_input) in recovered {
+        trace!("Re-queueing job {} after restart", job.id);
+        app_state.events.send([job.id.clone()], JobEvent::Queued);
+        app_state.thread_pool.resume(ExecuteParams {
+            job_id: job.id,
+            owner: job.owner,
+            priority: job.priority,
+            job_store: app_state.job_store.clone(),
+            dir: TempDir::new()?,
+            n_queries: program_input.n_queries(),
+            pow_bits: program_input.pow_bits(),
+            program_input,
+            events: app_state.events.clone(),
+            proof_cache: app_state.proof_cache.clone(),
+        })?;
+    }
+
+    tokio::spawn(janitor(app_state.job_store.clone()));
+
     async fn ok_handler() -> &'static str {
         "OK"
     }
@@ -85,10 +222,25 @@ pub async fn start(args: Args) -> Result<(), ProverError> {
         .route("/", get(ok_handler))
         .route("/verify", post(verify_proof))
         .route("/get-job/:id", get(get_job))
+        .route("/job/:id", delete(cancel_job))
+        .route("/job/:id/logs", get(get_job_logs))
+        .route("/job/:id/artifacts/:artifact", get(get_artifact))
+        .route("/jobs", get(list_jobs))
+        .route("/batch/:id", get(get_batch))
+        .route("/admin/cache", delete(purge_cache))
+        // Programs are capped like uploads, they are the bulk of a submission
+        .route(
+            "/programs",
+            get(list_programs)
+                .post(register_program)
+                .layer(DefaultBodyLimit::max(app_state.max_upload_bytes)),
+        )
+        .route("/programs/:hash", delete(delete_program))
         .route("/sse", get(sse_handler))
         .with_state(app_state.clone())
         .nest("/", auth(app_state.clone()))
         .nest("/prove", prove::router(app_state.clone()))
+        .nest("/run", run::router(app_state.clone()))
         .layer(middleware::from_extractor::<TempDirHandle>());
 
     let address: SocketAddr = format!("{}:{}", args.host, args.port)
@@ -1,57 +1,260 @@
-use crate::{auth::jwt::Claims, server::AppState};
+use crate::{
+    auth::jwt::Claims,
+    errors::ProverError,
+    server::AppState,
+    utils::job::{is_finished, Job},
+};
 use async_stream::stream;
 use axum::{
     extract::{Query, State},
-    response::{sse::KeepAlive, Sse},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
 };
-use common::models::JobStatus;
+use common::models::{BatchId, JobEvent, JobEventMessage, JobId, JobStatus};
 use futures::Stream;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tracing::info;
 
+// How often the followed jobs are looked up, to stop waiting for those that expired
+const EXPIRY_CHECK: Duration = Duration::from_secs(60);
+
+/// Either the job or the batch whose jobs to follow.
 #[derive(Deserialize, Serialize)]
 pub struct JobParams {
-    job_id: u64,
+    job_id: Option<JobId>,
+    batch_id: Option<BatchId>,
 }
+
+/// Streams the events of a job or of the jobs of a batch until all of them finished or expired.
+///
+/// A new client first gets the current state of every job. A client reconnecting with the
+/// `Last-Event-ID` header gets the events it missed instead, or the current state when they are
+/// no longer kept.
 pub async fn sse_handler(
     State(state): State<AppState>,
     Query(params): Query<JobParams>,
-    _claims: Claims,
-) -> Sse<impl Stream<Item = Result<axum::response::sse::Event, Infallible>>> {
+    headers: HeaderMap,
+    claims: Claims,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ProverError> {
     info!("SSE handler connected");
-    let mut rx = state.sse_tx.lock().await.subscribe();
-    let job_id = params.job_id;
+    // Subscribed before reading the jobs so that no later event is missed
+    let (mut rx, subscribed_at) = state.events.subscribe();
+    let jobs = followed_jobs(&state, params, &claims).await?;
+    let followed: HashSet<JobId> = jobs.iter().map(|job| job.id.clone()).collect();
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    let missed = last_event_id.and_then(|last_id| state.events.since(last_id, subscribed_at));
 
-    let job_status = state
-        .job_store
-        .get_job(job_id)
-        .await
-        .map_or(JobStatus::Unknown, |j| j.status);
-
-    let stream = stream! {
-        if matches!(job_status.clone(), JobStatus::Completed | JobStatus::Failed) {
-            yield Ok(axum::response::sse::Event::default().data(serde_json::to_string(&(job_status, job_id)).unwrap()));
-            return;
-        }
-        while let Ok(message) = rx.recv().await {
-            match serde_json::from_str::<(JobStatus, u64)>(&message) {
-                Ok((status, received_job_id)) => {
-                    if job_id == received_job_id {
-                        info!("Sending message: {}", message);
-                        yield Ok(axum::response::sse::Event::default().data(message));
-                        // If the job is completed or failed, break the loop to stop sending events
-                        if matches!(status, JobStatus::Completed | JobStatus::Failed) {
-                            info!("Job {} completed or failed, stopping SSE.", received_job_id);
-                            break;
-                        }
+    let mut initial = Vec::new();
+    let mut waiting = HashSet::new();
+    match missed {
+        Some(missed) => {
+            // Jobs finished before `Last-Event-ID` were already reported to the client
+            waiting.extend(
+                jobs.iter()
+                    .filter(|job| !is_finished(&job.status))
+                    .map(|job| job.id.clone()),
+            );
+            // Jobs finishing while they were read are reported with the live events
+            if let Some(later) = state.events.since(subscribed_at, u64::MAX) {
+                waiting.extend(
+                    later
+                        .iter()
+                        .filter(|message| followed.contains(&message.job_id))
+                        .map(|message| message.job_id.clone()),
+                );
+            }
+            for message in missed {
+                if followed.contains(&message.job_id) {
+                    if message.event.is_final() {
+                        waiting.remove(&message.job_id);
                     } else {
-                        info!("Ignoring message for job {} as it doesn't match requested job IDs ", received_job_id);
+                        waiting.insert(message.job_id.clone());
                     }
+                    initial.push(message);
                 }
-                Err(e) => println!("Failed to deserialize job status: {}", e),
+            }
+        }
+        None => {
+            for job in &jobs {
+                if !is_finished(&job.status) {
+                    waiting.insert(job.id.clone());
+                }
+                initial.push(snapshot(&state, job, Some(subscribed_at)).into());
+            }
+        }
+    }
+
+    let stream = stream! {
+        let mut positions = HashMap::new();
+        let mut expiry_check = tokio::time::interval(EXPIRY_CHECK);
+        for message in initial {
+            yield Ok(event(&message));
+        }
+        for message in queue_positions(&state, &waiting, &mut positions).await {
+            yield Ok(event(&message));
+        }
+        while !waiting.is_empty() {
+            let messages = tokio::select! {
+                received = rx.recv() => match received {
+                    // Events up to `subscribed_at` were sent above
+                    Ok(message) if message.id.is_some_and(|id| id <= subscribed_at) => continue,
+                    Ok(message) => vec![message],
+                    // Missed events are replaced with the current state of the jobs
+                    Err(RecvError::Lagged(_)) => forget_expired(&state, &mut waiting)
+                        .await
+                        .iter()
+                        .map(|job| snapshot(&state, job, None).into())
+                        .collect(),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = expiry_check.tick() => {
+                    forget_expired(&state, &mut waiting).await;
+                    Vec::new()
+                }
+            };
+            for message in messages {
+                if !waiting.contains(&message.job_id) {
+                    continue;
+                }
+                // Stop following jobs once they are completed, failed or cancelled
+                if message.event.is_final() {
+                    info!("Job {} completed, failed or cancelled", message.job_id);
+                    waiting.remove(&message.job_id);
+                    positions.remove(&message.job_id);
+                }
+                yield Ok(event(&message));
+            }
+            // Any event may move the queue, running or new jobs change the positions
+            for message in queue_positions(&state, &waiting, &mut positions).await {
+                yield Ok(event(&message));
             }
         }
     };
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// The job to follow, or every job of the batch
+async fn followed_jobs(
+    state: &AppState,
+    params: JobParams,
+    claims: &Claims,
+) -> Result<Vec<Job>, ProverError> {
+    match (params.job_id, params.batch_id) {
+        (Some(job_id), None) => {
+            let job = state
+                .job_store
+                .get_job(&job_id)
+                .await
+                .ok_or_else(|| ProverError::CustomError("Job not found".to_string()))?;
+            job.check_access(claims, &state.admin_keys)?;
+            Ok(vec![job])
+        }
+        (None, Some(batch_id)) => {
+            let batch = state
+                .job_store
+                .get_batch(&batch_id)
+                .await
+                .ok_or_else(|| ProverError::NotFound(format!("Batch {batch_id}")))?;
+            batch.check_access(claims, &state.admin_keys)?;
+            let mut jobs = Vec::new();
+            for job_id in &batch.jobs {
+                jobs.extend(state.job_store.get_job(job_id).await);
+            }
+            Ok(jobs)
+        }
+        _ => Err(ProverError::CustomError(
+            "Expected either a job_id or a batch_id".to_string(),
+        )),
+    }
+}
+
+// Stops waiting for the jobs the store forgot, expired jobs get no further event. The jobs
+// still there are returned.
+async fn forget_expired(state: &AppState, waiting: &mut HashSet<JobId>) -> Vec<Job> {
+    let mut jobs = Vec::new();
+    for job_id in waiting.clone() {
+        match state.job_store.get_job(&job_id).await {
+            Some(job) => jobs.push(job),
+            None => {
+                info!("Job {} expired", job_id);
+                waiting.remove(&job_id);
+            }
+        }
+    }
+    jobs
+}
+
+// The event standing for the current state of a job
+fn snapshot(state: &AppState, job: &Job, id: Option<u64>) -> JobEventMessage {
+    let event = match job.status {
+        JobStatus::Pending => JobEvent::Queued,
+        JobStatus::Running => state
+            .events
+            .phase(&job.id)
+            .unwrap_or(JobEvent::RunningTrace),
+        JobStatus::Completed => {
+            match serde_json::from_str(job.result.as_deref().unwrap_or_default()) {
+                Ok(result) => JobEvent::Completed { result },
+                Err(e) => JobEvent::Failed {
+                    error: format!("Failed to read the result: {e}"),
+                    details: None,
+                },
+            }
+        }
+        JobStatus::Failed => JobEvent::Failed {
+            error: job
+                .result
+                .clone()
+                .unwrap_or_else(|| "Unknown error".to_string()),
+            details: job.error.clone(),
+        },
+        _ => JobEvent::Cancelled,
+    };
+    JobEventMessage {
+        id,
+        job_id: job.id.clone(),
+        event,
+    }
+}
+
+// Position events of the queued jobs whose position changed since the last ones sent. Jobs
+// attached to an execution wait where the job running it does.
+async fn queue_positions(
+    state: &AppState,
+    waiting: &HashSet<JobId>,
+    sent: &mut HashMap<JobId, usize>,
+) -> Vec<JobEventMessage> {
+    let mut messages = Vec::new();
+    for job_id in waiting {
+        let leader = state.job_store.leader(job_id).await;
+        let Some(position) = state.thread_pool.queue_position(&leader) else {
+            sent.remove(job_id);
+            continue;
+        };
+        if sent.insert(job_id.clone(), position) != Some(position) {
+            messages.push(JobEventMessage {
+                id: None,
+                job_id: job_id.clone(),
+                event: JobEvent::Position { position },
+            });
+        }
+    }
+    messages
+}
+
+fn event(message: &JobEventMessage) -> Event {
+    let event = Event::default()
+        .event(message.event.name())
+        .data(serde_json::to_string(message).unwrap());
+    match message.id {
+        Some(id) => event.id(id.to_string()),
+        None => event,
+    }
 }
@@ -0,0 +1,102 @@
+use common::models::FailureReason;
+use std::{future::pending, time::Duration};
+use tokio::time::{interval, sleep};
+
+// How often the resident memory of a child process is sampled
+const MEMORY_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Limits applied to every job, `None` disables the respective limit.
+#[derive(Debug, Clone, Default)]
+pub struct JobLimits {
+    pub run_timeout: Option<Duration>,
+    pub prove_timeout: Option<Duration>,
+    /// Maximum `n_steps` of the execution, checked before proving
+    pub max_steps: Option<u64>,
+    /// Maximum resident memory of a runner or prover process in bytes
+    pub max_memory: Option<u64>,
+}
+
+impl JobLimits {
+    pub fn run(&self) -> ProcessLimits {
+        ProcessLimits {
+            timeout: self.run_timeout,
+            max_memory: self.max_memory,
+            max_steps: self.max_steps,
+        }
+    }
+    pub fn prove(&self) -> ProcessLimits {
+        ProcessLimits {
+            timeout: self.prove_timeout,
+            max_memory: self.max_memory,
+            max_steps: None,
+        }
+    }
+}
+
+/// Limits of a single subprocess.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessLimits {
+    pub timeout: Option<Duration>,
+    pub max_memory: Option<u64>,
+    /// Maximum steps of an execution, only the VM runner stops once it reaches them
+    pub max_steps: Option<u64>,
+}
+
+impl ProcessLimits {
+    /// Resolves once the process with `pid` exceeds one of the limits, never if there are none.
+    pub async fn exceeded(&self, pid: Option<u32>) -> FailureReason {
+        let timeout = async {
+            match self.timeout {
+                Some(timeout) => sleep(timeout).await,
+                None => pending().await,
+            }
+        };
+        let memory = async {
+            let (Some(pid), Some(max_memory)) = (pid, self.max_memory) else {
+                return pending().await;
+            };
+            let mut interval = interval(MEMORY_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if resident_memory(pid).is_some_and(|memory| memory > max_memory) {
+                    return;
+                }
+            }
+        };
+        tokio::select! {
+            _ = timeout => FailureReason::TimedOut,
+            _ = memory => FailureReason::ResourceExceeded,
+        }
+    }
+}
+
+// Reads VmRSS from procfs, so the memory limit is only enforced on Linux
+fn resident_memory(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let kilobytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_limit() {
+        let limits = ProcessLimits {
+            timeout: None,
+            max_memory: Some(1024),
+            max_steps: None,
+        };
+        // The test process itself is well above one kilobyte
+        let reason = limits.exceeded(Some(std::process::id())).await;
+        assert_eq!(reason, FailureReason::ResourceExceeded);
+    }
+}
@@ -1,103 +1,160 @@
-use crate::{errors::ProverError, threadpool::prove::prove, utils::job::JobStore};
+use crate::{
+    backend::Backend,
+    errors::ProverError,
+    runner::Runner,
+    threadpool::prove::prove,
+    utils::{cache::ProofCache, events::JobEvents, job::JobStore},
+};
+use common::models::{JobId, JobPriority, RunResult};
 
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 use tempfile::TempDir;
 use tokio::{
     spawn,
-    sync::{broadcast::Sender, mpsc, Mutex},
+    sync::{Semaphore, TryAcquireError},
     task::JoinHandle,
 };
 use tracing::trace;
 
+pub mod limits;
 pub mod prove;
+pub mod queue;
 pub mod run;
 
-pub use run::CairoVersionedInput;
-
-type ReceiverType = Arc<
-    Mutex<
-        mpsc::Receiver<(
-            u64,
-            JobStore,
-            TempDir,
-            CairoVersionedInput,
-            Arc<Mutex<Sender<String>>>,
-            Option<u32>,
-            Option<u32>,
-        )>,
-    >,
->;
-type SenderType = Option<
-    mpsc::Sender<(
-        u64,
-        JobStore,
-        TempDir,
-        CairoVersionedInput,
-        Arc<Mutex<Sender<String>>>,
-        Option<u32>,
-        Option<u32>,
-    )>,
->;
+pub use limits::JobLimits;
+pub use queue::JobQueue;
+pub use run::{CairoVersionedInput, UploadedInput};
+
 pub struct ExecuteParams {
-    pub job_id: u64,
+    pub job_id: JobId,
+    /// Key the job is scheduled under, jobs of different owners are dispatched round-robin
+    pub owner: String,
+    pub priority: JobPriority,
     pub job_store: JobStore,
     pub dir: TempDir,
     pub program_input: CairoVersionedInput,
-    pub sse_tx: Arc<Mutex<Sender<String>>>,
+    pub events: JobEvents,
     pub n_queries: Option<u32>,
     pub pow_bits: Option<u32>,
+    /// Where the result goes once the proof completes
+    pub proof_cache: ProofCache,
 }
 pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: SenderType,
+    // Taken out on shutdown, so that no lock is held while waiting for the workers
+    workers: std::sync::Mutex<Vec<Worker>>,
+    queue: Arc<JobQueue>,
+    // Execute-only runs happen in the request, at most one per worker at a time
+    run_slots: Semaphore,
+    runner: Runner,
+    limits: JobLimits,
 }
 
 impl ThreadPool {
-    pub fn new(size: usize) -> ThreadPool {
+    /// Starts `size` workers, submissions beyond `max_queue_depth` pending jobs are rejected
+    /// with a hint to retry after `retry_after` seconds.
+    pub fn new(
+        size: usize,
+        max_queue_depth: usize,
+        retry_after: u64,
+        limits: JobLimits,
+        backend: Backend,
+        runner: Runner,
+    ) -> ThreadPool {
         assert!(size > 0);
 
-        let (sender, receiver) = mpsc::channel(100);
-
-        let receiver = Arc::new(Mutex::new(receiver));
+        let queue = Arc::new(JobQueue::new(max_queue_depth, retry_after));
 
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(
+                id,
+                Arc::clone(&queue),
+                limits.clone(),
+                backend.clone(),
+                runner.clone(),
+            ));
         }
 
         ThreadPool {
-            workers,
-            sender: Some(sender),
+            workers: std::sync::Mutex::new(workers),
+            queue,
+            run_slots: Semaphore::new(size),
+            runner,
+            limits,
         }
     }
 
-    pub async fn execute(&self, params: ExecuteParams) -> Result<(), ProverError> {
-        self.sender
-            .as_ref()
-            .ok_or(ProverError::CustomError(
-                "Thread pool is shutdown".to_string(),
-            ))?
-            .send((
-                params.job_id,
-                params.job_store,
-                params.dir,
-                params.program_input,
-                params.sse_tx,
-                params.n_queries,
-                params.pow_bits,
-            ))
-            .await?;
-        Ok(())
+    /// A pool without workers, so that queued jobs stay in the queue for tests to inspect.
+    #[cfg(test)]
+    pub(crate) fn without_workers(max_queue_depth: usize) -> ThreadPool {
+        ThreadPool {
+            workers: Default::default(),
+            queue: Arc::new(JobQueue::new(max_queue_depth, 1)),
+            run_slots: Semaphore::new(1),
+            runner: Runner::default(),
+            limits: JobLimits::default(),
+        }
     }
 
-    pub async fn shutdown(&mut self) -> Result<(), ProverError> {
-        if let Some(sender) = self.sender.take() {
-            drop(sender); // Dropping the sender signals that no more messages will be sent
-        }
+    /// Queues a new job, failing right away when the queue is full.
+    pub fn execute(&self, params: ExecuteParams) -> Result<(), ProverError> {
+        self.queue.try_push(params)
+    }
+
+    /// Queues a job recovered after a restart, which doesn't count against the depth limit.
+    pub fn resume(&self, params: ExecuteParams) -> Result<(), ProverError> {
+        self.queue.push(params)
+    }
+
+    /// Checks whether a job would be accepted, before doing the work of creating it.
+    pub fn check_capacity(&self) -> Result<(), ProverError> {
+        self.queue.check_capacity()
+    }
+
+    /// Checks whether `n` jobs submitted together would all be accepted.
+    pub fn check_capacity_for(&self, n: usize) -> Result<(), ProverError> {
+        self.queue.check_capacity_for(n)
+    }
+
+    /// Number of jobs a worker will pick up before `job_id`, `None` once it left the queue.
+    pub fn queue_position(&self, job_id: &JobId) -> Option<usize> {
+        self.queue.position(job_id)
+    }
+
+    /// Moves a queued job up to `priority`, for a job of a higher class attaching to it.
+    pub fn raise_priority(&self, job_id: &JobId, priority: JobPriority) -> bool {
+        self.queue.raise_priority(job_id, priority)
+    }
+
+    /// Executes a program in `dir` without queueing a job or proving, for dry runs.
+    ///
+    /// Fails with `QueueFull` when as many runs as there are workers are already going on.
+    pub async fn run_only(
+        &self,
+        program_input: &CairoVersionedInput,
+        dir: &Path,
+    ) -> Result<RunResult, ProverError> {
+        let _permit = self.run_slots.try_acquire().map_err(|e| match e {
+            TryAcquireError::Closed => ProverError::ShuttingDown,
+            TryAcquireError::NoPermits => ProverError::QueueFull(self.queue.retry_after()),
+        })?;
+        run::run_only(program_input, dir, &self.runner, &self.limits).await
+    }
+
+    /// Drops a cancelled job from the queue so that it doesn't take up a worker.
+    pub fn remove(&self, job_id: &JobId) -> bool {
+        self.queue.remove(job_id)
+    }
+
+    pub async fn shutdown(&self) -> Result<(), ProverError> {
+        // Closing the queue signals that no more jobs will be queued
+        self.queue.close();
+        self.run_slots.close();
 
         // Wait for each worker to finish its current task
-        for worker in &mut self.workers {
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+        for mut worker in workers {
             if let Some(handle) = worker.thread.take() {
                 if let Err(e) = handle.await {
                     eprintln!("Error waiting for worker: {:?}", e);
@@ -116,26 +173,25 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(id: usize, receiver: ReceiverType) -> Worker {
+    fn new(
+        id: usize,
+        queue: Arc<JobQueue>,
+        limits: JobLimits,
+        backend: Backend,
+        runner: Runner,
+    ) -> Worker {
         let thread = spawn(async move {
             loop {
-                let message = receiver.lock().await.recv().await;
+                let message = queue.pop().await;
                 match message {
-                    Some((job_id, job_store, dir, program_input, sse_tx, n_queries, pow_bits)) => {
+                    Some(params) => {
                         trace!("Worker {id} got a job; executing.");
 
-                        if let Err(e) = prove(
-                            job_id,
-                            job_store,
-                            dir,
-                            program_input,
-                            sse_tx,
-                            n_queries,
-                            pow_bits,
-                        )
-                        .await
-                        {
-                            eprintln!("Worker {id} encountered an error: {:?}", e);
+                        if let Err(e) = prove(params, &limits, &backend, &runner).await {
+                            match e {
+                                ProverError::Cancelled => trace!("Worker {id} job was cancelled."),
+                                e => eprintln!("Worker {id} encountered an error: {:?}", e),
+                            }
                         }
 
                         trace!("Worker {id} finished the job.");
@@ -1,122 +1,227 @@
-use super::run::RunPaths;
-use super::CairoVersionedInput;
+use super::run::{check_exit, RunPaths};
+use super::{CairoVersionedInput, ExecuteParams, JobLimits};
+use crate::backend::{Backend, ProverBackend};
 use crate::errors::ProverError;
-use crate::utils::{config::Template, job::JobStore};
-use cairo_proof_parser::json_parser::proof_from_annotations;
-use cairo_proof_parser::output::ExtractOutputResult;
-use cairo_proof_parser::program::{CairoVersion, ExtractProgramResult};
-use cairo_proof_parser::{self, ProofJSON};
-use common::models::{JobStatus, ProverResult};
+use crate::runner::Runner;
+use crate::utils::{
+    cache,
+    config::{ProgramPublicInputAsNSteps, Template},
+    events::JobEvents,
+    job::JobStore,
+};
+use common::models::{
+    CommandLog, FailureReason, JobError, JobEvent, JobId, JobPhase, JobStatus, ProverResult,
+};
 use serde_json::Value;
 use std::fs;
-use std::path::PathBuf;
-use std::str::FromStr;
-use std::sync::Arc;
-use tempfile::TempDir;
-use tokio::process::Command;
-use tokio::sync::broadcast::Sender;
-use tokio::sync::Mutex;
+use std::path::{Path, PathBuf};
+use tokio::sync::watch;
 
 pub async fn prove(
-    job_id: u64,
-    job_store: JobStore,
-    dir: TempDir,
-    program_input: CairoVersionedInput,
-    sse_tx: Arc<Mutex<Sender<String>>>,
-    n_queries: Option<u32>,
-    pow_bits: Option<u32>,
+    params: ExecuteParams,
+    limits: &JobLimits,
+    backend: &Backend,
+    runner: &Runner,
 ) -> Result<(), ProverError> {
-    job_store
-        .update_job_status(job_id, JobStatus::Running, None)
-        .await;
-
-    let paths = ProvePaths::new(dir);
-
-    program_input
-        .prepare_and_run(&RunPaths::from(&paths))
+    let ExecuteParams {
+        job_id,
+        job_store,
+        dir,
+        program_input,
+        events,
+        n_queries,
+        pow_bits,
+        proof_cache,
+        ..
+    } = params;
+    let mut cancel = job_store.cancellation(&job_id).await;
+    // Jobs cancelled while still queued are dropped here
+    if *cancel.borrow() {
+        return Err(ProverError::Cancelled);
+    }
+    let running = job_store
+        .update_job_status(&job_id, JobStatus::Running, None)
         .await?;
-    Template::generate_from_public_input_file(&paths.public_input_file, n_queries, pow_bits)?
-        .save_to_file(&paths.params_file)?;
+    events.send(running, JobEvent::RunningTrace);
+    // Kept past the job for artifact downloads, the retention policy decides when it goes
+    let workdir = dir.into_path();
+    job_store.set_workdir(&job_id, workdir.clone()).await?;
 
-    let prove_status = paths.prove_command().spawn()?.wait().await?;
-    let result = fs::read_to_string(&paths.proof_path)?;
-    let proof: Value = serde_json::from_str(&result)?;
-    let final_result = serde_json::to_string_pretty(&proof)?;
-    let sender = sse_tx.lock().await;
-
-    if prove_status.success() {
-        let prover_result = match program_input {
-            CairoVersionedInput::Cairo(_cairo_input) => {
-                prover_result(final_result, CairoVersion::Cairo)?
-            }
-            CairoVersionedInput::Cairo0(_cairo0_input) => {
-                prover_result(final_result, CairoVersion::Cairo0)?
+    let job = ProveJob {
+        job_id: &job_id,
+        job_store: &job_store,
+        events: &events,
+        program_input: &program_input,
+        n_queries,
+        pow_bits,
+        limits,
+        backend,
+        runner,
+    };
+    // Jobs attached to this execution are notified like the job itself
+    match job.run(ProvePaths::in_dir(&workdir), &mut cancel).await {
+        Ok(prover_result) => {
+            let result = serde_json::to_string_pretty(&prover_result)?;
+            match cache::key(&program_input) {
+                Ok(key) => proof_cache.insert(key, result.clone()).await,
+                Err(e) => tracing::warn!("Failed to cache the result of job {}: {}", job_id, e),
             }
-        };
-        job_store
-            .update_job_status(
-                job_id,
-                JobStatus::Completed,
-                serde_json::to_string_pretty(&prover_result).ok(),
-            )
-            .await;
-        if sender.receiver_count() > 0 {
-            sender
-                .send(serde_json::to_string(&(JobStatus::Completed, job_id))?)
-                .unwrap();
+            let updated = job_store
+                .update_job_status(&job_id, JobStatus::Completed, Some(result))
+                .await?;
+            events.send(
+                updated,
+                JobEvent::Completed {
+                    result: prover_result,
+                },
+            );
         }
-    } else {
-        job_store
-            .update_job_status(job_id, JobStatus::Failed, Some(final_result))
-            .await;
-        if sender.receiver_count() > 0 {
-            sender
-                .send(serde_json::to_string(&(JobStatus::Failed, job_id))?)
-                .unwrap();
+        Err(JobFailure::Cancelled) => return Err(ProverError::Cancelled),
+        // Every failure ends the job, otherwise it would stay running and SSE clients would hang
+        Err(JobFailure::Failed(error)) => {
+            tracing::warn!("Job {} failed: {}", job_id, error);
+            let updated = job_store.fail_job(&job_id, error.clone()).await?;
+            events.send(
+                updated,
+                JobEvent::Failed {
+                    error: error.to_string(),
+                    details: Some(error),
+                },
+            );
         }
     }
-    Ok(())
+    job_store.clean_workdirs().await
 }
 
-fn prover_result(proof: String, cairo_version: CairoVersion) -> Result<ProverResult, ProverError> {
-    let proof_json = serde_json::from_str::<ProofJSON>(&proof)?;
-    let proof_from_annotations = proof_from_annotations(proof_json)?;
-    let ExtractProgramResult { program_hash, .. } = if cairo_version == CairoVersion::Cairo0 {
-        proof_from_annotations.extract_program(CairoVersion::Cairo0)?
-    } else {
-        proof_from_annotations.extract_program(CairoVersion::Cairo)?
-    };
-    let ExtractOutputResult {
-        program_output,
-        program_output_hash,
-    } = proof_from_annotations.extract_output()?;
-    let serialized_proof = proof_from_annotations.to_felts();
-    let prover_result = ProverResult {
-        proof: proof.clone(),
-        program_hash,
-        program_output,
-        program_output_hash,
-        serialized_proof,
-    };
-    Ok(prover_result)
+enum JobFailure {
+    Cancelled,
+    Failed(JobError),
+}
+
+// Attributes an error to the phase it happened in, cancellation is not a failure
+fn in_phase(phase: JobPhase) -> impl FnOnce(ProverError) -> JobFailure {
+    move |e| match e {
+        ProverError::Cancelled => JobFailure::Cancelled,
+        e => JobFailure::Failed(JobError {
+            phase,
+            message: e.to_string(),
+            exit_code: None,
+            reason: None,
+        }),
+    }
+}
+
+struct ProveJob<'a> {
+    job_id: &'a JobId,
+    job_store: &'a JobStore,
+    events: &'a JobEvents,
+    program_input: &'a CairoVersionedInput,
+    n_queries: Option<u32>,
+    pow_bits: Option<u32>,
+    limits: &'a JobLimits,
+    backend: &'a Backend,
+    runner: &'a Runner,
+}
+
+impl ProveJob<'_> {
+    async fn run(
+        &self,
+        paths: ProvePaths,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<ProverResult, JobFailure> {
+        let run_paths = RunPaths::from(&paths);
+        self.program_input
+            .prepare(&run_paths)
+            .map_err(in_phase(JobPhase::Prepare))?;
+        let execution = self
+            .program_input
+            .execute(self.runner, &run_paths, self.limits.run(), cancel)
+            .await
+            .map_err(in_phase(JobPhase::Run))?;
+        for log in &execution.logs {
+            self.record(log, JobPhase::Run).await?;
+        }
+        self.check_steps(&paths)?;
+
+        Template::generate_from_public_input_file(
+            &paths.public_input_file,
+            self.n_queries,
+            self.pow_bits,
+            self.program_input.prover_params(),
+        )
+        .and_then(|template| template.save_to_file(&paths.params_file))
+        .map_err(in_phase(JobPhase::Template))?;
+
+        let proving = self.job_store.execution(self.job_id).await;
+        self.events.send(proving, JobEvent::RunningProve);
+        let prove_log = self
+            .backend
+            .prove(
+                &paths,
+                self.program_input.prover_config(),
+                self.limits.prove(),
+                cancel,
+            )
+            .await
+            .map_err(in_phase(JobPhase::Prove))?;
+        self.record(&prove_log, JobPhase::Prove).await?;
+
+        let result = self.parse(&paths).map_err(in_phase(JobPhase::Parse))?;
+        Ok(ProverResult {
+            layout: Some(execution.layout),
+            ..result
+        })
+    }
+    // Stores the subprocess output before checking its exit code so failures keep their logs
+    async fn record(&self, log: &CommandLog, phase: JobPhase) -> Result<(), JobFailure> {
+        self.job_store
+            .append_log(self.job_id, log)
+            .await
+            .map_err(in_phase(phase))?;
+        check_exit(log, phase).map_err(JobFailure::Failed)
+    }
+    // Refuses to prove executions longer than allowed, proving is the expensive part
+    fn check_steps(&self, paths: &ProvePaths) -> Result<(), JobFailure> {
+        let Some(max_steps) = self.limits.max_steps else {
+            return Ok(());
+        };
+        let n_steps = ProgramPublicInputAsNSteps::read_from_file(&paths.public_input_file)
+            .map_err(in_phase(JobPhase::Run))?
+            .n_steps;
+        if u64::from(n_steps) > max_steps {
+            return Err(JobFailure::Failed(JobError {
+                phase: JobPhase::Run,
+                message: format!("Execution took {n_steps} steps, the limit is {max_steps}"),
+                exit_code: None,
+                reason: Some(FailureReason::ResourceExceeded),
+            }));
+        }
+        Ok(())
+    }
+    fn parse(&self, paths: &ProvePaths) -> Result<ProverResult, ProverError> {
+        let result = fs::read_to_string(&paths.proof_path)?;
+        let proof: Value = serde_json::from_str(&result)?;
+        let final_result = serde_json::to_string_pretty(&proof)?;
+        self.backend
+            .extract(final_result, self.program_input.cairo_version())
+    }
 }
 
 #[derive(Debug, Clone)]
-pub(super) struct ProvePaths {
-    pub(super) program_input: PathBuf,
-    pub(super) program: PathBuf,
-    pub(super) proof_path: PathBuf,
-    pub(super) trace_file: PathBuf,
-    pub(super) memory_file: PathBuf,
-    pub(super) public_input_file: PathBuf,
-    pub(super) private_input_file: PathBuf,
-    pub(super) params_file: PathBuf,
-    pub(super) config_file: PathBuf,
+pub(crate) struct ProvePaths {
+    pub(crate) program_input: PathBuf,
+    pub(crate) program: PathBuf,
+    pub(crate) proof_path: PathBuf,
+    pub(crate) trace_file: PathBuf,
+    pub(crate) memory_file: PathBuf,
+    pub(crate) public_input_file: PathBuf,
+    pub(crate) private_input_file: PathBuf,
+    pub(crate) params_file: PathBuf,
+    pub(crate) config_file: PathBuf,
 }
 
 impl ProvePaths {
-    pub fn new(base_dir: TempDir) -> Self {
-        let path = base_dir.into_path();
+    pub fn in_dir(path: &Path) -> Self {
         Self {
             program_input: path.join("program_input.json"),
             program: path.join("program.json"),
@@ -126,23 +231,63 @@ impl ProvePaths {
             public_input_file: path.join("program_public_input.json"),
             private_input_file: path.join("program_private_input.json"),
             params_file: path.join("cpu_air_params.json"),
-            config_file: PathBuf::from_str("config/cpu_air_prover_config.json").unwrap(),
+            config_file: path.join("cpu_air_prover_config.json"),
         }
     }
-    pub fn prove_command(&self) -> Command {
-        let mut command = Command::new("cpu_air_prover");
-        command
-            .arg("--out_file")
-            .arg(&self.proof_path)
-            .arg("--private_input_file")
-            .arg(&self.private_input_file)
-            .arg("--public_input_file")
-            .arg(&self.public_input_file)
-            .arg("--prover_config_file")
-            .arg(&self.config_file)
-            .arg("--parameter_file")
-            .arg(&self.params_file)
-            .arg("-generate-annotations");
-        command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{cache::ProofCache, job::tests::cairo_input};
+    use common::models::JobPriority;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_failed_run_marks_job_failed() {
+        let job_store = JobStore::default();
+        let input = cairo_input();
+        let job_id = job_store
+            .create_job("0x01", JobPriority::Normal, &input)
+            .await
+            .unwrap();
+        let events = JobEvents::default();
+        let (mut rx, _) = events.subscribe();
+
+        // The null program can't be run, whether or not the runner is installed
+        let params = ExecuteParams {
+            job_id: job_id.clone(),
+            owner: "0x01".to_string(),
+            priority: JobPriority::Normal,
+            job_store: job_store.clone(),
+            dir: TempDir::new().unwrap(),
+            program_input: input,
+            events,
+            n_queries: None,
+            pow_bits: None,
+            proof_cache: ProofCache::default(),
+        };
+        prove(
+            params,
+            &JobLimits::default(),
+            &Backend::default(),
+            &Runner::default(),
+        )
+        .await
+        .unwrap();
+
+        let job = job_store.get_job(&job_id).await.unwrap();
+        assert!(matches!(job.status, JobStatus::Failed));
+        assert_eq!(job.error.unwrap().phase, JobPhase::Run);
+        assert!(matches!(
+            rx.recv().await.unwrap().event,
+            JobEvent::RunningTrace
+        ));
+        let message = rx.recv().await.unwrap();
+        assert_eq!(message.job_id, job_id);
+        let JobEvent::Failed { details, .. } = &message.event else {
+            panic!("Expected a failed event, got {}", message.event.name());
+        };
+        assert_eq!(details.as_ref().unwrap().phase, JobPhase::Run);
     }
 }
@@ -0,0 +1,396 @@
+use common::models::{JobId, JobPriority};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+};
+use tokio::sync::Notify;
+
+use super::ExecuteParams;
+use crate::errors::ProverError;
+
+/// Pending jobs, dispatched by priority class and round-robin between keys within a class.
+///
+/// A key submitting many jobs only gets every n-th slot of its class, where n is the number of
+/// keys with queued jobs, so it cannot starve the others.
+pub struct JobQueue {
+    inner: Mutex<QueueInner>,
+    notify: Notify,
+    max_depth: usize,
+    // Seconds a rejected client is asked to wait before submitting again
+    retry_after: u64,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    // Highest priority first
+    classes: BTreeMap<Reverse<JobPriority>, PriorityClass>,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct PriorityClass {
+    jobs: HashMap<String, VecDeque<ExecuteParams>>,
+    // Owners with queued jobs, the front one is served next
+    rotation: VecDeque<String>,
+}
+
+impl JobQueue {
+    pub fn new(max_depth: usize, retry_after: u64) -> Self {
+        Self {
+            inner: Mutex::default(),
+            notify: Notify::new(),
+            max_depth,
+            retry_after,
+        }
+    }
+
+    /// Fails without blocking when the queue is closed or already holds `max_depth` jobs.
+    pub fn check_capacity(&self) -> Result<(), ProverError> {
+        self.check_capacity_for(1)
+    }
+
+    /// Same as `check_capacity` for `n` jobs submitted together, which fit all or not at all.
+    pub fn check_capacity_for(&self, n: usize) -> Result<(), ProverError> {
+        self.inner.lock().unwrap().check_capacity(self, n)
+    }
+
+    /// Queues a job unless the queue is closed or full.
+    pub fn try_push(&self, params: ExecuteParams) -> Result<(), ProverError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.check_capacity(self, 1)?;
+        inner.push(params);
+        drop(inner);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Queues a job regardless of the depth limit, for jobs that were accepted before.
+    pub fn push(&self, params: ExecuteParams) -> Result<(), ProverError> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return Err(ProverError::ShuttingDown);
+        }
+        inner.push(params);
+        drop(inner);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Waits for the next job, returns `None` once the queue is closed and drained.
+    pub async fn pop(&self) -> Option<ExecuteParams> {
+        loop {
+            // Created before checking so a push in between still wakes us up
+            let notified = self.notify.notified();
+            {
+                let mut inner = self.inner.lock().unwrap();
+                if let Some(params) = inner.pop() {
+                    return Some(params);
+                }
+                if inner.closed {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Removes a job that hasn't been picked up by a worker yet.
+    pub fn remove(&self, job_id: &JobId) -> bool {
+        self.inner.lock().unwrap().take(job_id).is_some()
+    }
+
+    /// Moves a queued job up to `priority` unless it is queued with that class or a higher one
+    /// already. Returns whether it moved.
+    pub fn raise_priority(&self, job_id: &JobId, priority: JobPriority) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let queued_below = inner
+            .classes
+            .iter()
+            .any(|(class, jobs)| class.0 < priority && jobs.find(job_id).is_some());
+        if !queued_below {
+            return false;
+        }
+        let Some(mut params) = inner.take(job_id) else {
+            return false;
+        };
+        params.priority = priority;
+        inner.push(params);
+        true
+    }
+
+    /// Number of jobs a worker will pick up before this one, `None` if it isn't queued.
+    pub fn position(&self, job_id: &JobId) -> Option<usize> {
+        let inner = self.inner.lock().unwrap();
+        let mut ahead = 0;
+        for class in inner.classes.values() {
+            match class.position(job_id) {
+                Some(position) => return Some(ahead + position),
+                None => ahead += class.len(),
+            }
+        }
+        None
+    }
+
+    /// Seconds a client turned away for lack of capacity should wait before retrying.
+    pub fn retry_after(&self) -> u64 {
+        self.retry_after
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stops accepting work, workers finish the queued jobs and exit.
+    pub fn close(&self) {
+        self.inner.lock().unwrap().closed = true;
+        self.notify.notify_waiters();
+    }
+}
+
+impl QueueInner {
+    fn push(&mut self, params: ExecuteParams) {
+        let class = self.classes.entry(Reverse(params.priority)).or_default();
+        let jobs = class.jobs.entry(params.owner.clone()).or_default();
+        if jobs.is_empty() {
+            class.rotation.push_back(params.owner.clone());
+        }
+        jobs.push_back(params);
+    }
+
+    fn take(&mut self, job_id: &JobId) -> Option<ExecuteParams> {
+        for (key, class) in self.classes.iter_mut() {
+            let Some((owner, index)) = class.find(job_id) else {
+                continue;
+            };
+            let jobs = class.jobs.get_mut(&owner).unwrap();
+            let params = jobs.remove(index);
+            if jobs.is_empty() {
+                class.jobs.remove(&owner);
+                class.rotation.retain(|o| *o != owner);
+            }
+            if class.jobs.is_empty() {
+                let key = *key;
+                self.classes.remove(&key);
+            }
+            return params;
+        }
+        None
+    }
+
+    fn check_capacity(&self, queue: &JobQueue, n: usize) -> Result<(), ProverError> {
+        if self.closed {
+            return Err(ProverError::ShuttingDown);
+        }
+        // Waiting wouldn't help
+        if n > queue.max_depth {
+            return Err(ProverError::CustomError(format!(
+                "{n} jobs don't fit in a queue of {}",
+                queue.max_depth
+            )));
+        }
+        if self.len() + n > queue.max_depth {
+            return Err(ProverError::QueueFull(queue.retry_after));
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.classes.values().map(PriorityClass::len).sum()
+    }
+
+    fn pop(&mut self) -> Option<ExecuteParams> {
+        let mut entry = self.classes.first_entry()?;
+        let params = entry.get_mut().pop();
+        if entry.get().jobs.is_empty() {
+            entry.remove();
+        }
+        params
+    }
+}
+
+impl PriorityClass {
+    fn pop(&mut self) -> Option<ExecuteParams> {
+        let owner = self.rotation.pop_front()?;
+        let jobs = self.jobs.get_mut(&owner)?;
+        let params = jobs.pop_front();
+        if jobs.is_empty() {
+            self.jobs.remove(&owner);
+        } else {
+            self.rotation.push_back(owner);
+        }
+        params
+    }
+
+    fn len(&self) -> usize {
+        self.jobs.values().map(VecDeque::len).sum()
+    }
+
+    fn find(&self, job_id: &JobId) -> Option<(String, usize)> {
+        self.jobs.iter().find_map(|(owner, jobs)| {
+            let index = jobs.iter().position(|params| params.job_id == *job_id)?;
+            Some((owner.clone(), index))
+        })
+    }
+
+    // The job is the `index`-th of its owner, every owner ahead of it in the rotation gets
+    // one more turn before it than the owners behind it
+    fn position(&self, job_id: &JobId) -> Option<usize> {
+        let (owner, index) = self.find(job_id)?;
+        let turn = self.rotation.iter().position(|o| *o == owner)?;
+        let others: usize = self
+            .rotation
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| **o != owner)
+            .map(|(i, o)| {
+                let turns = index + usize::from(i < turn);
+                self.jobs[o].len().min(turns)
+            })
+            .sum();
+        Some(index + others)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{
+        cache::ProofCache,
+        events::JobEvents,
+        job::{tests::cairo_input, JobStore},
+    };
+    use tempfile::TempDir;
+
+    fn params(id: &str, owner: &str, priority: JobPriority) -> ExecuteParams {
+        ExecuteParams {
+            job_id: JobId::from(id.to_string()),
+            owner: owner.to_string(),
+            priority,
+            job_store: JobStore::default(),
+            dir: TempDir::new().unwrap(),
+            program_input: cairo_input(),
+            events: JobEvents::default(),
+            n_queries: None,
+            pow_bits: None,
+            proof_cache: ProofCache::default(),
+        }
+    }
+
+    fn id(id: &str) -> JobId {
+        JobId::from(id.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_between_keys() {
+        let queue = JobQueue::new(100, 10);
+        for i in 0..3 {
+            queue
+                .push(params(&format!("a{i}"), "a", JobPriority::Normal))
+                .unwrap();
+        }
+        queue.push(params("b0", "b", JobPriority::Normal)).unwrap();
+        queue.push(params("b1", "b", JobPriority::Normal)).unwrap();
+        queue.push(params("c0", "c", JobPriority::Normal)).unwrap();
+
+        let expected = ["a0", "b0", "c0", "a1", "b1", "a2"];
+        for (position, job) in expected.iter().enumerate() {
+            assert_eq!(queue.position(&id(job)), Some(position));
+        }
+        for job in expected {
+            assert_eq!(queue.pop().await.unwrap().job_id, id(job));
+        }
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_priority_classes() {
+        let queue = JobQueue::new(100, 10);
+        queue.push(params("low", "a", JobPriority::Low)).unwrap();
+        queue
+            .push(params("normal", "a", JobPriority::Normal))
+            .unwrap();
+        queue.push(params("high", "b", JobPriority::High)).unwrap();
+
+        assert_eq!(queue.position(&id("low")), Some(2));
+        assert_eq!(queue.pop().await.unwrap().job_id, id("high"));
+        assert_eq!(queue.pop().await.unwrap().job_id, id("normal"));
+        assert_eq!(queue.pop().await.unwrap().job_id, id("low"));
+    }
+
+    #[tokio::test]
+    async fn test_remove_and_close() {
+        let queue = JobQueue::new(100, 10);
+        queue.push(params("a0", "a", JobPriority::Normal)).unwrap();
+        queue.push(params("b0", "b", JobPriority::Normal)).unwrap();
+        assert!(queue.remove(&id("a0")));
+        assert!(!queue.remove(&id("a0")));
+        assert_eq!(queue.position(&id("b0")), Some(0));
+
+        queue.close();
+        assert!(matches!(
+            queue.push(params("b1", "b", JobPriority::Normal)),
+            Err(ProverError::ShuttingDown)
+        ));
+        assert_eq!(queue.pop().await.unwrap().job_id, id("b0"));
+        assert!(queue.pop().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_raise_priority() {
+        let queue = JobQueue::new(100, 10);
+        queue.push(params("a0", "a", JobPriority::Normal)).unwrap();
+        queue.push(params("b0", "b", JobPriority::Low)).unwrap();
+        assert_eq!(queue.position(&id("b0")), Some(1));
+
+        assert!(!queue.raise_priority(&id("b0"), JobPriority::Low));
+        assert!(queue.raise_priority(&id("b0"), JobPriority::High));
+        assert!(!queue.raise_priority(&id("b0"), JobPriority::Normal));
+        assert!(!queue.raise_priority(&id("c0"), JobPriority::High));
+        assert_eq!(queue.position(&id("b0")), Some(0));
+        let params = queue.pop().await.unwrap();
+        assert_eq!(params.job_id, id("b0"));
+        assert_eq!(params.priority, JobPriority::High);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_depth() {
+        let queue = JobQueue::new(2, 10);
+        queue.check_capacity_for(2).unwrap();
+        assert!(matches!(
+            queue.check_capacity_for(3),
+            Err(ProverError::CustomError(_))
+        ));
+        queue
+            .try_push(params("a0", "a", JobPriority::Normal))
+            .unwrap();
+        assert!(matches!(
+            queue.check_capacity_for(2),
+            Err(ProverError::QueueFull(10))
+        ));
+        queue
+            .try_push(params("b0", "b", JobPriority::High))
+            .unwrap();
+        assert!(matches!(
+            queue.try_push(params("c0", "c", JobPriority::High)),
+            Err(ProverError::QueueFull(10))
+        ));
+        assert!(matches!(
+            queue.check_capacity(),
+            Err(ProverError::QueueFull(10))
+        ));
+
+        // Recovered jobs were accepted before and are queued past the limit
+        queue.push(params("a1", "a", JobPriority::Normal)).unwrap();
+        assert_eq!(queue.len(), 3);
+
+        queue.pop().await.unwrap();
+        queue.pop().await.unwrap();
+        queue.check_capacity().unwrap();
+    }
+}
@@ -1,24 +1,111 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
 
-use common::prover_input::{Cairo0ProverInput, CairoProverInput};
+use common::models::{
+    CairoVersion, CommandLog, FailureReason, JobError, JobPhase, JobPriority, RunResult,
+};
+use common::prover_input::{
+    Cairo0ProverInput, CairoProverInput, ProverConfig, ProverParams, SubmissionParams,
+};
+use serde::{
+    de::{Error as _, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use starknet_types_core::felt::Felt;
-use tokio::process::Command;
-use tracing::trace;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+    sync::watch,
+    task::JoinHandle,
+};
 
 use crate::errors::ProverError;
+use crate::runner::{CairoRunner, Runner};
+use crate::utils::{layout, public_input::PublicInput};
 
+use super::limits::{JobLimits, ProcessLimits};
 use super::prove::ProvePaths;
+#[derive(Clone, Serialize, Deserialize)]
 pub enum CairoVersionedInput {
     Cairo(CairoProverInput),
     Cairo0(Cairo0ProverInput),
+    Upload(UploadedInput),
+}
+
+/// An input kept in files instead of memory, a multipart upload or an item of a batch sharing
+/// its program with the others.
+///
+/// The files are already in the form the runners read, see [`CairoVersionedInput::prepare`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UploadedInput {
+    pub cairo_version: CairoVersion,
+    pub program: PathBuf,
+    pub program_input: PathBuf,
+    pub params: SubmissionParams,
+    /// Builtins the program declares, all an automatic layout needs to know of it
+    pub builtins: Vec<String>,
+    /// Digest of the program and input files, the one the same submission as JSON gets, see
+    /// [`submission_digest`](crate::utils::cache::submission_digest)
+    pub digest: String,
 }
 
 impl CairoVersionedInput {
-    pub async fn prepare_and_run(&self, paths: &'_ RunPaths<'_>) -> Result<(), ProverError> {
-        self.prepare(paths)?;
-        self.run(paths).await
+    pub fn n_queries(&self) -> Option<u32> {
+        match self {
+            CairoVersionedInput::Cairo(input) => input.n_queries,
+            CairoVersionedInput::Cairo0(input) => input.n_queries,
+            CairoVersionedInput::Upload(input) => input.params.n_queries,
+        }
+    }
+    pub fn pow_bits(&self) -> Option<u32> {
+        match self {
+            CairoVersionedInput::Cairo(input) => input.pow_bits,
+            CairoVersionedInput::Cairo0(input) => input.pow_bits,
+            CairoVersionedInput::Upload(input) => input.params.pow_bits,
+        }
+    }
+    pub fn priority(&self) -> Option<JobPriority> {
+        match self {
+            CairoVersionedInput::Cairo(input) => input.priority,
+            CairoVersionedInput::Cairo0(input) => input.priority,
+            CairoVersionedInput::Upload(input) => input.params.priority,
+        }
+    }
+    pub fn prover_config(&self) -> Option<&ProverConfig> {
+        match self {
+            CairoVersionedInput::Cairo(input) => input.prover_config.as_ref(),
+            CairoVersionedInput::Cairo0(input) => input.prover_config.as_ref(),
+            CairoVersionedInput::Upload(input) => input.params.prover_config.as_ref(),
+        }
+    }
+    pub fn prover_params(&self) -> Option<&ProverParams> {
+        match self {
+            CairoVersionedInput::Cairo(input) => input.prover_params.as_ref(),
+            CairoVersionedInput::Cairo0(input) => input.prover_params.as_ref(),
+            CairoVersionedInput::Upload(input) => input.params.prover_params.as_ref(),
+        }
+    }
+    pub fn layout(&self) -> &str {
+        match self {
+            CairoVersionedInput::Cairo(input) => &input.layout,
+            CairoVersionedInput::Cairo0(input) => &input.layout,
+            CairoVersionedInput::Upload(input) => &input.params.layout,
+        }
     }
-    fn prepare(&self, paths: &RunPaths<'_>) -> Result<(), ProverError> {
+    pub fn cairo_version(&self) -> CairoVersion {
+        match self {
+            CairoVersionedInput::Cairo(_) => CairoVersion::Cairo,
+            CairoVersionedInput::Cairo0(_) => CairoVersion::Cairo0,
+            CairoVersionedInput::Upload(input) => input.cairo_version.clone(),
+        }
+    }
+    /// Writes the program and its input to `paths`, in the form the runners read.
+    pub fn prepare(&self, paths: &RunPaths<'_>) -> Result<(), ProverError> {
         match self {
             CairoVersionedInput::Cairo(input) => {
                 let program = serde_json::to_string(&input.program)?;
@@ -33,74 +120,115 @@ impl CairoVersionedInput {
                 )?;
                 fs::write(paths.program, serde_json::to_string(&input.program)?)?;
             }
+            // Only jobs recovered after a restart read the files from elsewhere
+            CairoVersionedInput::Upload(input) => {
+                copy_if_moved(&input.program, paths.program)?;
+                copy_if_moved(&input.program_input, paths.program_input_path)?;
+            }
         }
         Ok(())
     }
-    async fn run(&self, paths: &RunPaths<'_>) -> Result<(), ProverError> {
-        match self {
-            CairoVersionedInput::Cairo(input) => {
-                trace!("Running cairo1-run");
-                let command = paths.cairo1_run_command(&input.layout);
-                command_run(command).await
-            }
-            CairoVersionedInput::Cairo0(input) => {
-                trace!("Running cairo0-run");
-                let command = paths.cairo0_run_command(&input.layout);
-                command_run(command).await
-            }
+    /// Runs the program with `runner`, see [`CairoRunner::run`].
+    pub async fn run(
+        &self,
+        runner: &Runner,
+        paths: &RunPaths<'_>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<CommandLog, ProverError> {
+        runner.run(self, paths, limits, cancel).await
+    }
+    /// Runs the prepared program, choosing the layout first when it is `auto`.
+    ///
+    /// An automatic layout is the smallest one providing the declared builtins, the program is
+    /// run once more with a larger layout when that shortens the trace. Logs are kept up to the
+    /// first failed run.
+    pub async fn execute(
+        &self,
+        runner: &Runner,
+        paths: &RunPaths<'_>,
+        limits: ProcessLimits,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> Result<Execution, ProverError> {
+        if self.layout() != layout::AUTO {
+            let log = self.run(runner, paths, limits, cancel).await?;
+            return Ok(Execution {
+                logs: vec![log],
+                layout: self.layout().to_string(),
+            });
         }
+        let builtins = layout::declared_builtins(self);
+        let smallest = layout::smallest(&builtins)?;
+        let log = self
+            .with_layout(smallest)
+            .run(runner, paths, limits, cancel)
+            .await?;
+        let mut execution = Execution {
+            logs: vec![log],
+            layout: smallest.to_string(),
+        };
+        if check_exit(&execution.logs[0], JobPhase::Run).is_err() {
+            return Ok(execution);
+        }
+        let public_input = PublicInput::read_from_file(paths.public_input_file)?;
+        if let Some(shorter) = layout::shorter_trace(&builtins, &public_input) {
+            let log = self
+                .with_layout(shorter)
+                .run(runner, paths, limits, cancel)
+                .await?;
+            execution.logs.push(log);
+            execution.layout = shorter.to_string();
+        }
+        Ok(execution)
+    }
+    fn with_layout(&self, layout: &str) -> Self {
+        let mut input = self.clone();
+        match &mut input {
+            CairoVersionedInput::Cairo(input) => input.layout = layout.to_string(),
+            CairoVersionedInput::Cairo0(input) => input.layout = layout.to_string(),
+            CairoVersionedInput::Upload(input) => input.params.layout = layout.to_string(),
+        }
+        input
     }
 }
 
-pub struct RunPaths<'a> {
-    trace_file: &'a PathBuf,
-    memory_file: &'a PathBuf,
-    public_input_file: &'a PathBuf,
-    private_input_file: &'a PathBuf,
-    program_input_path: &'a PathBuf,
-    program: &'a PathBuf,
-}
-
-impl RunPaths<'_> {
-    pub fn cairo1_run_command(&self, layout: &str) -> Command {
-        let mut command = Command::new("cairo1-run");
-        command
-            .arg("--trace_file")
-            .arg(self.trace_file)
-            .arg("--memory_file")
-            .arg(self.memory_file)
-            .arg("--layout")
-            .arg(layout)
-            .arg("--proof_mode")
-            .arg("--air_public_input")
-            .arg(self.public_input_file)
-            .arg("--air_private_input")
-            .arg(self.private_input_file)
-            .arg("--args_file")
-            .arg(self.program_input_path)
-            .arg(self.program);
-        command
-    }
-    pub fn cairo0_run_command(&self, layout: &str) -> Command {
-        let mut command = Command::new("cairo-run");
-        command
-            .arg("--trace_file")
-            .arg(self.trace_file)
-            .arg("--memory_file")
-            .arg(self.memory_file)
-            .arg("--layout")
-            .arg(layout)
-            .arg("--proof_mode")
-            .arg("--air_public_input")
-            .arg(self.public_input_file)
-            .arg("--air_private_input")
-            .arg(self.private_input_file)
-            .arg("--program_input")
-            .arg(self.program_input_path)
-            .arg("--program")
-            .arg(self.program);
-        command
+/// The runs of a program, see [`CairoVersionedInput::execute`].
+pub struct Execution {
+    pub logs: Vec<CommandLog>,
+    /// Layout of the last run, the one the files were written with
+    pub layout: String,
+}
+
+/// Executes `program_input` in `dir` and describes the execution, without proving it.
+///
+/// The run limits apply, `max_steps` doesn't since the step count is what callers want to learn.
+pub(crate) async fn run_only(
+    program_input: &CairoVersionedInput,
+    dir: &Path,
+    runner: &Runner,
+    limits: &JobLimits,
+) -> Result<RunResult, ProverError> {
+    let paths = ProvePaths::in_dir(dir);
+    let run_paths = RunPaths::from(&paths);
+    program_input.prepare(&run_paths)?;
+    // The client waiting for the response is the only one who can cancel, by going away
+    let (_cancel_tx, mut cancel) = watch::channel(false);
+    let execution = program_input
+        .execute(runner, &run_paths, limits.run(), &mut cancel)
+        .await?;
+    for log in &execution.logs {
+        check_exit(log, JobPhase::Run).map_err(ProverError::RunFailed)?;
     }
+    PublicInput::read_from_file(&paths.public_input_file)?.run_result()
+}
+
+pub struct RunPaths<'a> {
+    pub(crate) trace_file: &'a PathBuf,
+    pub(crate) memory_file: &'a PathBuf,
+    pub(crate) public_input_file: &'a PathBuf,
+    pub(crate) private_input_file: &'a PathBuf,
+    pub(crate) program_input_path: &'a PathBuf,
+    pub(crate) program: &'a PathBuf,
 }
 
 impl<'a> From<&'a ProvePaths> for RunPaths<'a> {
@@ -126,19 +254,103 @@ impl<'a> From<&'a ProvePaths> for RunPaths<'a> {
     }
 }
 
-async fn command_run(mut command: Command) -> Result<(), ProverError> {
+// Output beyond this size is dropped from the start, the end usually explains a failure
+const MAX_LOG_BYTES: usize = 64 * 1024;
+
+/// Runs `command` to completion, keeping the tail of its stdout and stderr.
+///
+/// A process exceeding `limits` is killed and reported through `CommandLog::killed`. A non-zero
+/// exit status is not an error here either, the caller records the log first.
+pub(crate) async fn command_run(
+    mut command: Command,
+    limits: ProcessLimits,
+    cancel: &mut watch::Receiver<bool>,
+) -> Result<CommandLog, ProverError> {
     command
         .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    let name = command
+        .as_std()
+        .get_program()
+        .to_string_lossy()
+        .into_owned();
+
+    let mut child = command.spawn()?;
+    let stdout = tokio::spawn(read_tail(child.stdout.take()));
+    let stderr = tokio::spawn(read_tail(child.stderr.take()));
+    let pid = child.id();
+    // Returning early on cancellation drops the child, which kills it
+    let (exit_code, killed) = tokio::select! {
+        status = child.wait() => (status?.code(), None),
+        reason = limits.exceeded(pid) => (None, Some(reason)),
+        Ok(_) = cancel.wait_for(|cancelled| *cancelled) => return Err(ProverError::Cancelled),
+    };
+    if killed.is_some() {
+        child.kill().await?;
+    }
+    let (stdout, stdout_truncated) = join_output(stdout).await?;
+    let (stderr, stderr_truncated) = join_output(stderr).await?;
+
+    Ok(CommandLog {
+        command: name,
+        exit_code,
+        stdout,
+        stderr,
+        truncated: stdout_truncated || stderr_truncated,
+        killed,
+    })
+}
 
-    let child = command.spawn()?;
-    let output = child.wait_with_output().await?;
+/// Fails with the captured stderr when the command exited unsuccessfully.
+pub(crate) fn check_exit(log: &CommandLog, phase: JobPhase) -> Result<(), JobError> {
+    let message = match (log.killed, log.exit_code) {
+        (None, Some(0)) => return Ok(()),
+        (Some(FailureReason::TimedOut), _) => format!("{} exceeded its time limit", log.command),
+        (Some(FailureReason::ResourceExceeded), _) => {
+            format!("{} exceeded its memory limit", log.command)
+        }
+        (None, _) => log.stderr.trim_end().to_string(),
+    };
+    Err(JobError {
+        phase,
+        message,
+        exit_code: log.exit_code,
+        reason: log.killed,
+    })
+}
+
+async fn join_output(
+    handle: JoinHandle<std::io::Result<(String, bool)>>,
+) -> Result<(String, bool), ProverError> {
+    handle
+        .await
+        .map_err(|e| ProverError::InternalServerError(e.to_string()))?
+        .map_err(ProverError::from)
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(ProverError::CustomError(stderr.into()));
+async fn read_tail(reader: Option<impl AsyncRead + Unpin>) -> std::io::Result<(String, bool)> {
+    let Some(mut reader) = reader else {
+        return Ok((String::new(), false));
+    };
+    let mut tail = VecDeque::new();
+    let mut truncated = false;
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        tail.extend(&buf[..n]);
+        if tail.len() > MAX_LOG_BYTES {
+            tail.drain(..tail.len() - MAX_LOG_BYTES);
+            truncated = true;
+        }
     }
-    Ok(())
+    Ok((
+        String::from_utf8_lossy(tail.make_contiguous()).into_owned(),
+        truncated,
+    ))
 }
 
 pub fn prepare_input(felts: &[Felt]) -> String {
@@ -150,6 +362,47 @@ pub fn prepare_input(felts: &[Felt]) -> String {
         + "]"
 }
 
+/// Writes the JSON array of felts in `from` to `to` as [`prepare_input`] does, one felt at a
+/// time so that the input is never held in memory.
+pub fn prepare_input_file(from: &Path, to: &Path) -> Result<(), ProverError> {
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(File::open(from)?));
+    let mut writer = BufWriter::new(File::create(to)?);
+    deserializer.deserialize_seq(FeltList(&mut writer))?;
+    deserializer.end()?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Writes the felts of a JSON array in the `[1 2 3]` form `cairo1-run` reads
+struct FeltList<W>(W);
+
+impl<'de, W: Write> Visitor<'de> for FeltList<W> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of felts")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let mut write = |s: &str| self.0.write_all(s.as_bytes()).map_err(A::Error::custom);
+        write("[")?;
+        let mut separator = "";
+        while let Some(felt) = seq.next_element::<Felt>()? {
+            write(separator)?;
+            write(&felt.to_string())?;
+            separator = " ";
+        }
+        write("]")
+    }
+}
+
+fn copy_if_moved(from: &Path, to: &Path) -> Result<(), ProverError> {
+    if from != to {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
 #[test]
 fn test_prepare_input() {
     assert_eq!("[]", prepare_input(&[]));
@@ -159,3 +412,61 @@ fn test_prepare_input() {
         prepare_input(&[1.into(), 2.into(), 3.into(), 4.into()])
     );
 }
+
+#[test]
+fn test_prepare_input_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (from, to) = (dir.path().join("input.json"), dir.path().join("args"));
+    fs::write(&from, r#"["0x1", "0x2", "0xff"]"#).unwrap();
+    prepare_input_file(&from, &to).unwrap();
+    let felts = [1.into(), 2.into(), 255.into()];
+    assert_eq!(fs::read_to_string(&to).unwrap(), prepare_input(&felts));
+
+    fs::write(&from, "[]").unwrap();
+    prepare_input_file(&from, &to).unwrap();
+    assert_eq!(fs::read_to_string(&to).unwrap(), "[]");
+    // Felts are hex strings
+    fs::write(&from, "[1]").unwrap();
+    assert!(matches!(
+        prepare_input_file(&from, &to),
+        Err(ProverError::Parse(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_command_run_keeps_output_tail() {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("head -c 70000 /dev/zero | tr '\\0' a; echo done; echo failed >&2; exit 3");
+    let (_tx, mut cancel) = watch::channel(false);
+    let log = command_run(command, ProcessLimits::default(), &mut cancel)
+        .await
+        .unwrap();
+    assert_eq!(log.command, "sh");
+    assert_eq!(log.exit_code, Some(3));
+    assert!(log.truncated);
+    assert_eq!(log.stdout.len(), MAX_LOG_BYTES);
+    assert!(log.stdout.ends_with("done\n"));
+    assert_eq!(log.stderr, "failed\n");
+    let error = check_exit(&log, JobPhase::Run).unwrap_err();
+    assert_eq!(error.message, "failed");
+    assert_eq!(error.exit_code, Some(3));
+}
+
+#[tokio::test]
+async fn test_command_run_kills_on_timeout() {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("echo started; exec sleep 30");
+    let limits = ProcessLimits {
+        timeout: Some(std::time::Duration::from_millis(200)),
+        max_memory: None,
+        max_steps: None,
+    };
+    let (_tx, mut cancel) = watch::channel(false);
+    let log = command_run(command, limits, &mut cancel).await.unwrap();
+    assert_eq!(log.killed, Some(FailureReason::TimedOut));
+    assert_eq!(log.exit_code, None);
+    let error = check_exit(&log, JobPhase::Prove).unwrap_err();
+    assert_eq!(error.reason, Some(FailureReason::TimedOut));
+}
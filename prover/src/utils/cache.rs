@@ -0,0 +1,429 @@
+use axum::{extract::State, Json};
+use common::models::CairoVersion;
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufReader, Read},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    auth::{auth_errors::AuthError, jwt::Claims},
+    errors::ProverError,
+    server::AppState,
+    threadpool::{run::prepare_input, CairoVersionedInput},
+};
+
+/// Results of completed proofs, keyed by everything that determines the proof.
+///
+/// Entries expire after `ttl` and the least recently used ones are evicted once the results
+/// take more than `max_bytes`. The default cache is disabled and never stores anything.
+#[derive(Clone, Default)]
+pub struct ProofCache {
+    inner: Arc<Mutex<CacheInner>>,
+    max_bytes: usize,
+    ttl: Duration,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<String, CacheEntry>,
+    bytes: usize,
+}
+
+struct CacheEntry {
+    result: String,
+    inserted: Instant,
+    last_used: Instant,
+}
+
+impl ProofCache {
+    pub fn new(max_bytes: usize, ttl: Duration) -> Self {
+        Self {
+            inner: Arc::default(),
+            max_bytes,
+            ttl,
+        }
+    }
+    /// The serialized `ProverResult` of an earlier proof of the submission with this `key`.
+    pub async fn get(&self, key: &str) -> Option<String> {
+        if self.max_bytes == 0 {
+            return None;
+        }
+        let mut inner = self.inner.lock().await;
+        inner.clear_expired(self.ttl);
+        inner.entries.get_mut(key).map(|entry| {
+            entry.last_used = Instant::now();
+            entry.result.clone()
+        })
+    }
+    /// Stores the serialized `ProverResult` of a completed proof of the submission with `key`.
+    pub async fn insert(&self, key: String, result: String) {
+        if self.max_bytes == 0 || result.len() > self.max_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock().await;
+        inner.remove(&key);
+        inner.bytes += result.len();
+        let now = Instant::now();
+        inner.entries.insert(
+            key,
+            CacheEntry {
+                result,
+                inserted: now,
+                last_used: now,
+            },
+        );
+        inner.clear_expired(self.ttl);
+        while inner.bytes > self.max_bytes {
+            let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            inner.remove(&oldest);
+        }
+    }
+    /// Drops every entry, returning how many there were.
+    pub async fn purge(&self) -> usize {
+        let mut inner = self.inner.lock().await;
+        let purged = inner.entries.len();
+        *inner = CacheInner::default();
+        purged
+    }
+}
+
+impl CacheInner {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.result.len();
+        }
+    }
+    fn clear_expired(&mut self, ttl: Duration) {
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.inserted.elapsed() >= ttl)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            self.remove(&key);
+        }
+    }
+}
+
+/// Hash of everything that determines the proof of `input`, identical submissions share it.
+///
+/// The priority and the prover config only change how the proof is made and are left out. A
+/// submission is identified by [`submission_digest`] whether it came as JSON, as an upload or
+/// as an item of a batch.
+pub fn key(input: &CairoVersionedInput) -> Result<String, ProverError> {
+    let cairo_version = input.cairo_version();
+    let submission = match input {
+        CairoVersionedInput::Cairo(input) => submission_digest(
+            &program_digest(
+                &cairo_version,
+                serde_json::to_vec(&input.program)?.as_slice(),
+            )?,
+            &input_digest(
+                &cairo_version,
+                prepare_input(&input.program_input).as_bytes(),
+            )?,
+        ),
+        CairoVersionedInput::Cairo0(input) => submission_digest(
+            &program_digest(
+                &cairo_version,
+                serde_json::to_vec(&input.program)?.as_slice(),
+            )?,
+            &input_digest(
+                &cairo_version,
+                serde_json::to_vec(&input.program_input)?.as_slice(),
+            )?,
+        ),
+        CairoVersionedInput::Upload(input) => input.digest.clone(),
+    };
+    let canonical = json!({
+        "cairo_version": cairo_version,
+        "submission": submission,
+        "layout": input.layout(),
+        "n_queries": input.n_queries(),
+        "pow_bits": input.pow_bits(),
+        "prover_params": input.prover_params(),
+    });
+    let digest = Sha256::digest(serde_json::to_vec(&canonical)?);
+    Ok(prefix_hex::encode(digest.to_vec()))
+}
+
+/// Identifies a submission by the digests of its program and its program input.
+pub fn submission_digest(program: &[u8], program_input: &[u8]) -> String {
+    let hash = Sha256::new()
+        .chain_update(program)
+        .chain_update(program_input)
+        .finalize();
+    prefix_hex::encode(hash.to_vec())
+}
+
+/// Digest of the compiled program read from `program`, which doesn't depend on the layout of
+/// the JSON.
+///
+/// Only the fields the prover keeps of a program count, the debug info of Cairo 0 programs
+/// only maps the bytecode back to the sources and is left out. The program is read as it goes
+/// and never held in memory.
+pub fn program_digest(
+    cairo_version: &CairoVersion,
+    program: impl Read,
+) -> Result<Vec<u8>, ProverError> {
+    let fields: &[&str] = match cairo_version {
+        CairoVersion::Cairo0 => &[
+            "attributes",
+            "builtins",
+            "compiler_version",
+            "data",
+            "hints",
+            "identifiers",
+            "main_scope",
+            "prime",
+            "reference_manager",
+        ],
+        CairoVersion::Cairo => &[
+            "type_declarations",
+            "libfunc_declarations",
+            "statements",
+            "funcs",
+        ],
+    };
+    json_digest(program, Some(fields))
+}
+
+/// Digest of the program input read from `program_input`.
+///
+/// A Sierra program input is hashed in the form the runner reads, see [`prepare_input`], a
+/// Cairo 0 one as JSON like [`program_digest`] does.
+pub fn input_digest(
+    cairo_version: &CairoVersion,
+    mut program_input: impl Read,
+) -> Result<Vec<u8>, ProverError> {
+    match cairo_version {
+        CairoVersion::Cairo => {
+            let mut hasher = Sha256::new();
+            io::copy(&mut program_input, &mut hasher)?;
+            Ok(hasher.finalize().to_vec())
+        }
+        CairoVersion::Cairo0 => json_digest(program_input, None),
+    }
+}
+
+fn json_digest(json: impl Read, fields: Option<&[&str]>) -> Result<Vec<u8>, ProverError> {
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(json));
+    let digest = JsonDigest { fields }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(digest.to_vec())
+}
+
+// Hashes a JSON value by its structure, an object by the sorted hashes of its entries so that
+// neither the whitespace nor the order of the keys changes the digest. Only the entries named
+// in `fields` count when given.
+struct JsonDigest<'a> {
+    fields: Option<&'a [&'a str]>,
+}
+
+type Hash = [u8; 32];
+
+fn hash(tag: &[u8], contents: &[u8]) -> Hash {
+    Sha256::new()
+        .chain_update(tag)
+        .chain_update(contents)
+        .finalize()
+        .into()
+}
+
+impl<'de> DeserializeSeed<'de> for JsonDigest<'_> {
+    type Value = Hash;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Hash, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for JsonDigest<'_> {
+    type Value = Hash;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Hash, E> {
+        Ok(hash(b"null", &[]))
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Hash, E> {
+        Ok(hash(b"bool", &[v as u8]))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Hash, E> {
+        Ok(hash(b"number", v.to_string().as_bytes()))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Hash, E> {
+        Ok(hash(b"number", v.to_string().as_bytes()))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Hash, E> {
+        Ok(hash(b"number", v.to_string().as_bytes()))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Hash, E> {
+        Ok(hash(b"string", v.as_bytes()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Hash, A::Error> {
+        let mut hasher = Sha256::new_with_prefix(b"array");
+        while let Some(element) = seq.next_element_seed(JsonDigest { fields: None })? {
+            hasher.update(element);
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Hash, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if self
+                .fields
+                .is_some_and(|fields| !fields.contains(&key.as_str()))
+            {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            let value = map.next_value_seed(JsonDigest { fields: None })?;
+            entries.push(hash(&hash(b"string", key.as_bytes()), &value));
+        }
+        entries.sort_unstable();
+        Ok(hash(b"object", &entries.concat()))
+    }
+}
+
+/// Empties the proof cache, for admin keys only.
+pub async fn purge_cache(
+    State(app_state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<serde_json::Value>, ProverError> {
+    if !claims.is_admin(&app_state.admin_keys) {
+        return Err(ProverError::Auth(AuthError::Forbidden));
+    }
+    let purged = app_state.proof_cache.purge().await;
+    Ok(Json(json!({ "purged": purged })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{threadpool::UploadedInput, utils::job::tests::cairo_input};
+    use common::prover_input::{ProverParams, SubmissionParams};
+    use std::path::PathBuf;
+
+    fn with_params(params: ProverParams) -> CairoVersionedInput {
+        let CairoVersionedInput::Cairo(mut input) = cairo_input() else {
+            unreachable!()
+        };
+        input.prover_params = Some(params);
+        CairoVersionedInput::Cairo(input)
+    }
+
+    #[tokio::test]
+    async fn test_cache_key() {
+        let cache = ProofCache::new(1024, Duration::from_secs(60));
+        cache
+            .insert(key(&cairo_input()).unwrap(), "proof".to_string())
+            .await;
+        assert_eq!(
+            cache.get(&key(&cairo_input()).unwrap()).await.as_deref(),
+            Some("proof")
+        );
+
+        // The priority doesn't change the proof, the parameters do
+        let CairoVersionedInput::Cairo(mut input) = cairo_input() else {
+            unreachable!()
+        };
+        input.priority = Some(common::models::JobPriority::High);
+        let input = CairoVersionedInput::Cairo(input);
+        assert_eq!(key(&input).unwrap(), key(&cairo_input()).unwrap());
+        let input = with_params(ProverParams {
+            log_n_cosets: Some(4),
+            ..Default::default()
+        });
+        assert_ne!(key(&input).unwrap(), key(&cairo_input()).unwrap());
+
+        assert_eq!(cache.purge().await, 1);
+        assert!(cache.get(&key(&cairo_input()).unwrap()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cache_limits() {
+        let first = key(&cairo_input()).unwrap();
+        let second = key(&with_params(ProverParams {
+            log_n_cosets: Some(4),
+            ..Default::default()
+        }))
+        .unwrap();
+        let cache = ProofCache::new(10, Duration::from_secs(60));
+        cache.insert(first.clone(), "a".repeat(6)).await;
+        cache.insert(second.clone(), "b".repeat(6)).await;
+        // The least recently used result is evicted to make room
+        assert!(cache.get(&first).await.is_none());
+        assert!(cache.get(&second).await.is_some());
+        // Results larger than the whole cache are not stored
+        cache.insert(first.clone(), "a".repeat(11)).await;
+        assert!(cache.get(&first).await.is_none());
+
+        let cache = ProofCache::new(10, Duration::ZERO);
+        cache.insert(first.clone(), "a".to_string()).await;
+        assert!(cache.get(&first).await.is_none());
+
+        let disabled = ProofCache::default();
+        disabled.insert(first.clone(), String::new()).await;
+        assert!(disabled.get(&first).await.is_none());
+    }
+
+    #[test]
+    fn test_submission_digest() {
+        // The program of `cairo_input` uploaded with its keys in another order and a field the
+        // prover doesn't keep
+        let program = r#"{ "version": 1, "funcs": null, "statements": null,
+            "libfunc_declarations": null, "type_declarations": null }"#;
+        let upload = CairoVersionedInput::Upload(UploadedInput {
+            cairo_version: CairoVersion::Cairo,
+            program: PathBuf::new(),
+            program_input: PathBuf::new(),
+            params: SubmissionParams {
+                layout: "recursive".to_string(),
+                n_queries: Some(16),
+                pow_bits: Some(20),
+                ..Default::default()
+            },
+            builtins: vec![],
+            digest: submission_digest(
+                &program_digest(&CairoVersion::Cairo, program.as_bytes()).unwrap(),
+                &input_digest(&CairoVersion::Cairo, "[1 2]".as_bytes()).unwrap(),
+            ),
+        });
+        assert_eq!(key(&upload).unwrap(), key(&cairo_input()).unwrap());
+
+        let digest = |program: &str| program_digest(&CairoVersion::Cairo0, program.as_bytes());
+        let program = digest(r#"{"data": ["0x1"], "hints": {"a": 1, "b": [true, null]}}"#).unwrap();
+        // Neither the order of the keys nor the debug info count, the order of arrays does
+        let reordered =
+            r#"{"hints": {"b": [true, null], "a": 1}, "debug_info": {}, "data": ["0x1"]}"#;
+        assert_eq!(digest(reordered).unwrap(), program);
+        let swapped = r#"{"data": ["0x1"], "hints": {"a": 1, "b": [null, true]}}"#;
+        assert_ne!(digest(swapped).unwrap(), program);
+        assert!(digest("{} {}").is_err());
+    }
+}
@@ -1,8 +1,10 @@
+use common::prover_input::{ChannelHash, CommitmentHash, PowHash, ProverParams, ProverPreset};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fs::File;
 use std::io::{BufReader, Write};
 use std::path::PathBuf;
+use thiserror::Error;
 
 use crate::errors::ProverError;
 
@@ -23,15 +25,15 @@ struct Stark {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Template {
     field: String,
-    channel_hash: String,
-    commitment_hash: String,
+    channel_hash: ChannelHash,
+    commitment_hash: CommitmentHash,
     n_verifier_friendly_commitment_layers: u32,
-    pow_hash: String,
+    pow_hash: PowHash,
     statement: Value,
     stark: Stark,
     use_extension_field: bool,
     verifier_friendly_channel_updates: bool,
-    verifier_friendly_commitment_hash: String,
+    verifier_friendly_commitment_hash: CommitmentHash,
 }
 
 impl Template {
@@ -39,11 +41,11 @@ impl Template {
         file: &PathBuf,
         n_queries: Option<u32>,
         pow_bits: Option<u32>,
+        params: Option<&ProverParams>,
     ) -> Result<Self, ProverError> {
         Self::generate_from_public_input(
             ProgramPublicInputAsNSteps::read_from_file(file)?,
-            n_queries,
-            pow_bits,
+            Self::new(n_queries, pow_bits, params),
         )
     }
     pub fn save_to_file(&self, file: &PathBuf) -> Result<(), ProverError> {
@@ -52,33 +54,76 @@ impl Template {
             .write_all(json_string.as_bytes())
             .map_err(ProverError::from)
     }
-    fn generate_from_public_input(
-        public_input: ProgramPublicInputAsNSteps,
+    /// Parameters of the preset in `params`, with every field that is set overriding it.
+    pub fn new(
         n_queries: Option<u32>,
         pow_bits: Option<u32>,
+        params: Option<&ProverParams>,
+    ) -> Self {
+        let params = params.cloned().unwrap_or_default();
+        let mut template = match params.preset {
+            None | Some(ProverPreset::StarknetRecursive) => Self::default(),
+            Some(ProverPreset::EthereumVerifierFriendly) => Self::ethereum_verifier_friendly(),
+        };
+        let fri = &mut template.stark.fri;
+        fri.n_queries = n_queries.unwrap_or(fri.n_queries);
+        fri.proof_of_work_bits = pow_bits.unwrap_or(fri.proof_of_work_bits);
+        fri.last_layer_degree_bound = params
+            .last_layer_degree_bound
+            .unwrap_or(fri.last_layer_degree_bound);
+        let stark = &mut template.stark;
+        stark.log_n_cosets = params.log_n_cosets.unwrap_or(stark.log_n_cosets);
+        template.channel_hash = params.channel_hash.unwrap_or(template.channel_hash);
+        template.commitment_hash = params.commitment_hash.unwrap_or(template.commitment_hash);
+        template.pow_hash = params.pow_hash.unwrap_or(template.pow_hash);
+        template.n_verifier_friendly_commitment_layers = params
+            .n_verifier_friendly_commitment_layers
+            .unwrap_or(template.n_verifier_friendly_commitment_layers);
+        template.verifier_friendly_channel_updates = params
+            .verifier_friendly_channel_updates
+            .unwrap_or(template.verifier_friendly_channel_updates);
+        template.verifier_friendly_commitment_hash = params
+            .verifier_friendly_commitment_hash
+            .unwrap_or(template.verifier_friendly_commitment_hash);
+        template
+    }
+    /// Conjectured security of the proof, each query is worth the log of the blowup factor.
+    pub fn security_bits(&self) -> u32 {
+        let fri = &self.stark.fri;
+        fri.n_queries * self.stark.log_n_cosets + fri.proof_of_work_bits
+    }
+    fn generate_from_public_input(
+        public_input: ProgramPublicInputAsNSteps,
+        mut template: Self,
     ) -> Result<Self, ProverError> {
-        let mut template = Self::default();
-        if let Some(pow_bits) = pow_bits {
-            template.stark.fri.proof_of_work_bits = pow_bits;
-        }
-        if let Some(n_queries) = n_queries {
-            template.stark.fri.n_queries = n_queries;
-        }
         let fri_step_list =
-            public_input.calculate_fri_step_list(template.stark.fri.last_layer_degree_bound);
+            public_input.calculate_fri_step_list(template.stark.fri.last_layer_degree_bound)?;
         template.stark.fri.fri_step_list = fri_step_list;
         Ok(template)
     }
+    // Keccak everywhere, the Solidity verifier can't afford Poseidon
+    fn ethereum_verifier_friendly() -> Self {
+        let mut template = Self {
+            channel_hash: ChannelHash::Keccak256,
+            commitment_hash: CommitmentHash::Keccak256Masked160Msb,
+            n_verifier_friendly_commitment_layers: 0,
+            verifier_friendly_channel_updates: false,
+            verifier_friendly_commitment_hash: CommitmentHash::Keccak256Masked160Msb,
+            ..Self::default()
+        };
+        template.stark.log_n_cosets = 4;
+        template
+    }
 }
 
 impl core::default::Default for Template {
     fn default() -> Self {
         Template {
             field: "PrimeField0".to_string(),
-            channel_hash: "poseidon3".to_string(),
-            commitment_hash: "blake256_masked160_lsb".to_string(),
+            channel_hash: ChannelHash::Poseidon3,
+            commitment_hash: CommitmentHash::Blake256Masked160Lsb,
             n_verifier_friendly_commitment_layers: 9999,
-            pow_hash: "keccak256".to_string(),
+            pow_hash: PowHash::Keccak256,
             statement: serde_json::json!({ "page_hash": "pedersen" }),
             stark: Stark {
                 fri: StarkFri {
@@ -91,27 +136,165 @@ impl core::default::Default for Template {
             },
             use_extension_field: false,
             verifier_friendly_channel_updates: true,
-            verifier_friendly_commitment_hash: "poseidon3".to_string(),
+            verifier_friendly_commitment_hash: CommitmentHash::Poseidon3,
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
-struct ProgramPublicInputAsNSteps {
-    n_steps: u32,
+pub(crate) struct ProgramPublicInputAsNSteps {
+    pub(crate) n_steps: u32,
+}
+
+// Every layout fills 16 rows of the CPU component per step
+const LOG_CPU_ROWS_PER_STEP: u32 = 4;
+// Largest FRI step the verifiers accept
+const MAX_FRI_STEP: u32 = 4;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FriError {
+    #[error("The trace has {0} steps, Stone only proves a power of two")]
+    StepsNotPowerOfTwo(u32),
+    #[error("Last layer degree bound {0} is not a power of two")]
+    DegreeBoundNotPowerOfTwo(u32),
+    #[error(
+        "Last layer degree bound {degree_bound} is larger than the trace degree {trace_degree}"
+    )]
+    DegreeBoundTooLarge {
+        degree_bound: u32,
+        trace_degree: u64,
+    },
 }
 
 impl ProgramPublicInputAsNSteps {
     pub fn read_from_file(input_file: &PathBuf) -> Result<Self, ProverError> {
         serde_json::from_reader(BufReader::new(File::open(input_file)?)).map_err(ProverError::from)
     }
-    fn calculate_fri_step_list(&self, degree_bound: u32) -> Vec<u32> {
-        let fri_degree = ((self.n_steps as f64 / degree_bound as f64).log(2.0).round() as u32) + 4;
+    /// FRI layer sizes folding the trace down to `degree_bound`.
+    ///
+    /// Stone requires the log of the last layer degree bound plus the steps to equal the log of
+    /// the trace degree. The list starts with the customary 0 and folds by up to 16 per layer.
+    fn calculate_fri_step_list(&self, degree_bound: u32) -> Result<Vec<u32>, FriError> {
+        if !self.n_steps.is_power_of_two() {
+            return Err(FriError::StepsNotPowerOfTwo(self.n_steps));
+        }
+        if !degree_bound.is_power_of_two() {
+            return Err(FriError::DegreeBoundNotPowerOfTwo(degree_bound));
+        }
+        let log_trace_degree = self.n_steps.ilog2() + LOG_CPU_ROWS_PER_STEP;
+        let fri_degree = log_trace_degree.checked_sub(degree_bound.ilog2()).ok_or(
+            FriError::DegreeBoundTooLarge {
+                degree_bound,
+                trace_degree: 1 << log_trace_degree,
+            },
+        )?;
         let mut steps = vec![0];
-        steps.extend(vec![4; (fri_degree / 4) as usize]);
-        if fri_degree % 4 != 0 {
-            steps.push(fri_degree % 4);
+        steps.extend(vec![MAX_FRI_STEP; (fri_degree / MAX_FRI_STEP) as usize]);
+        if fri_degree % MAX_FRI_STEP != 0 {
+            steps.push(fri_degree % MAX_FRI_STEP);
+        }
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    fn fri_step_list(n_steps: u32, degree_bound: u32) -> Result<Vec<u32>, FriError> {
+        ProgramPublicInputAsNSteps { n_steps }.calculate_fri_step_list(degree_bound)
+    }
+
+    #[test]
+    fn test_fri_step_list() {
+        assert_eq!(fri_step_list(1 << 16, 128).unwrap(), [0, 4, 4, 4, 1]);
+        assert_eq!(fri_step_list(1 << 15, 128).unwrap(), [0, 4, 4, 4]);
+        assert_eq!(fri_step_list(8, 128).unwrap(), [0]);
+        assert_eq!(
+            fri_step_list(4, 128),
+            Err(FriError::DegreeBoundTooLarge {
+                degree_bound: 128,
+                trace_degree: 64
+            })
+        );
+        assert_eq!(
+            fri_step_list(3000, 128),
+            Err(FriError::StepsNotPowerOfTwo(3000))
+        );
+        assert_eq!(
+            fri_step_list(1 << 16, 100),
+            Err(FriError::DegreeBoundNotPowerOfTwo(100))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_fri_degree_constraint(log_n_steps in 0..32u32, log_bound in 0..32u32) {
+            let trace_degree = 1u64 << (log_n_steps + LOG_CPU_ROWS_PER_STEP);
+            let degree_bound = 1u32 << log_bound;
+            match fri_step_list(1 << log_n_steps, degree_bound) {
+                Ok(steps) => {
+                    prop_assert_eq!(steps[0], 0);
+                    prop_assert!(steps.iter().all(|step| *step <= MAX_FRI_STEP));
+                    // Only the last layer folds by less than the maximum
+                    let inner = steps.iter().skip(1).rev().skip(1);
+                    prop_assert!(inner.into_iter().all(|step| *step == MAX_FRI_STEP));
+                    let degree = u64::from(degree_bound) << steps.iter().sum::<u32>();
+                    prop_assert_eq!(degree, trace_degree);
+                }
+                Err(e) => {
+                    prop_assert!(u64::from(degree_bound) > trace_degree, "{}", e);
+                }
+            }
         }
-        steps
+
+        #[test]
+        fn test_fri_rejects_padding(n_steps in any::<u32>(), degree_bound in any::<u32>()) {
+            let result = fri_step_list(n_steps, degree_bound);
+            if !n_steps.is_power_of_two() {
+                prop_assert_eq!(result, Err(FriError::StepsNotPowerOfTwo(n_steps)));
+            } else if !degree_bound.is_power_of_two() {
+                prop_assert_eq!(result, Err(FriError::DegreeBoundNotPowerOfTwo(degree_bound)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_parameters() {
+        let template = serde_json::to_value(Template::new(Some(16), Some(20), None)).unwrap();
+        assert_eq!(template["channel_hash"], "poseidon3");
+        assert_eq!(template["commitment_hash"], "blake256_masked160_lsb");
+        assert_eq!(template["verifier_friendly_commitment_hash"], "poseidon3");
+        assert_eq!(template["stark"]["fri"]["n_queries"], 16);
+        assert_eq!(template["stark"]["fri"]["proof_of_work_bits"], 20);
+    }
+
+    #[test]
+    fn test_preset_with_overrides() {
+        let params: ProverParams = serde_json::from_value(json!({
+            "preset": "ethereum-verifier-friendly",
+            "last_layer_degree_bound": 64,
+            "pow_hash": "blake256"
+        }))
+        .unwrap();
+        let template = Template::new(Some(18), None, Some(&params));
+        assert_eq!(template.security_bits(), 18 * 4 + 30);
+        let template = serde_json::to_value(template).unwrap();
+        assert_eq!(template["channel_hash"], "keccak256");
+        assert_eq!(template["commitment_hash"], "keccak256_masked160_msb");
+        assert_eq!(template["n_verifier_friendly_commitment_layers"], 0);
+        assert_eq!(template["pow_hash"], "blake256");
+        assert_eq!(template["stark"]["fri"]["last_layer_degree_bound"], 64);
+        assert_eq!(template["stark"]["log_n_cosets"], 4);
+    }
+
+    #[test]
+    fn test_unknown_parameter() {
+        let params = serde_json::from_value::<ProverParams>(json!({ "channel_hash": "sha256" }));
+        assert!(params.is_err());
+        let params = serde_json::from_value::<ProverParams>(json!({ "log_cosets": 2 }));
+        assert!(params.is_err());
     }
 }
@@ -0,0 +1,129 @@
+use common::models::{JobEvent, JobEventMessage, JobId};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast;
+
+// Events kept for clients resuming with `Last-Event-ID`, and buffered for slow clients
+const BACKLOG_SIZE: usize = 100;
+
+/// Broadcasts job events to SSE clients, keeping the latest ones so clients can resume.
+///
+/// Event ids grow across restarts, they start at the startup time in microseconds.
+#[derive(Clone)]
+pub struct JobEvents {
+    tx: broadcast::Sender<Arc<JobEventMessage>>,
+    backlog: Arc<Mutex<Backlog>>,
+}
+
+struct Backlog {
+    last_id: u64,
+    events: VecDeque<Arc<JobEventMessage>>,
+    // Latest phase of each running job, for clients connecting while it runs
+    phases: HashMap<JobId, JobEvent>,
+}
+
+impl Default for JobEvents {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(BACKLOG_SIZE).0,
+            backlog: Arc::new(Mutex::new(Backlog {
+                last_id: chrono::Utc::now().timestamp_micros() as u64,
+                events: VecDeque::with_capacity(BACKLOG_SIZE),
+                phases: HashMap::new(),
+            })),
+        }
+    }
+}
+
+impl JobEvents {
+    /// Sends `event` for each of `job_ids`, which is a no-op while no client listens.
+    pub fn send(&self, job_ids: impl IntoIterator<Item = JobId>, event: JobEvent) {
+        let mut backlog = self.backlog.lock().unwrap();
+        for job_id in job_ids {
+            match event {
+                JobEvent::RunningTrace | JobEvent::RunningProve => {
+                    backlog.phases.insert(job_id.clone(), event.clone());
+                }
+                _ => {
+                    backlog.phases.remove(&job_id);
+                }
+            }
+            backlog.last_id += 1;
+            let message = Arc::new(JobEventMessage {
+                id: Some(backlog.last_id),
+                job_id,
+                event: event.clone(),
+            });
+            if backlog.events.len() == BACKLOG_SIZE {
+                backlog.events.pop_front();
+            }
+            backlog.events.push_back(message.clone());
+            // Sent under the lock so that receivers get the events in id order
+            let _ = self.tx.send(message);
+        }
+    }
+    /// Receives the events sent after the returned id.
+    pub fn subscribe(&self) -> (broadcast::Receiver<Arc<JobEventMessage>>, u64) {
+        let backlog = self.backlog.lock().unwrap();
+        (self.tx.subscribe(), backlog.last_id)
+    }
+    /// Events sent after `last_id` up to `until`, `None` once some of them left the backlog.
+    pub fn since(&self, last_id: u64, until: u64) -> Option<Vec<Arc<JobEventMessage>>> {
+        let backlog = self.backlog.lock().unwrap();
+        let oldest = backlog
+            .events
+            .front()
+            .and_then(|message| message.id)
+            .unwrap_or(backlog.last_id + 1);
+        // Ids past the latest one come from another server
+        if last_id > backlog.last_id || oldest > last_id + 1 {
+            return None;
+        }
+        Some(
+            backlog
+                .events
+                .iter()
+                .filter(|message| message.id.is_some_and(|id| id > last_id && id <= until))
+                .cloned()
+                .collect(),
+        )
+    }
+    /// The phase a running job is in, `None` for jobs that aren't running.
+    pub fn phase(&self, job_id: &JobId) -> Option<JobEvent> {
+        self.backlog.lock().unwrap().phases.get(job_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backlog() {
+        let events = JobEvents::default();
+        let job_id = JobId::from(1);
+        let (mut rx, start) = events.subscribe();
+        events.send([job_id.clone()], JobEvent::Queued);
+        events.send([job_id.clone()], JobEvent::RunningTrace);
+        assert!(matches!(
+            events.phase(&job_id),
+            Some(JobEvent::RunningTrace)
+        ));
+        assert_eq!(rx.try_recv().unwrap().id, Some(start + 1));
+
+        let (_, latest) = events.subscribe();
+        let missed = events.since(start + 1, latest).unwrap();
+        assert_eq!(missed.len(), 1);
+        assert!(matches!(missed[0].event, JobEvent::RunningTrace));
+        assert!(events.since(latest + 1, latest).is_none());
+
+        for _ in 0..BACKLOG_SIZE {
+            events.send([job_id.clone()], JobEvent::Cancelled);
+        }
+        assert!(events.phase(&job_id).is_none());
+        // The events after `start` are gone
+        assert!(events.since(start, latest).is_none());
+    }
+}
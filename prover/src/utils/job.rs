@@ -1,34 +1,135 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use common::models::{JobStatus, ProverResult};
+use common::{
+    models::{
+        BatchId, BatchJob, BatchResponse, CairoVersion, CommandLog, JobError, JobEvent, JobId,
+        JobLogs, JobPriority, JobStatus, JobSummary, ListJobsResponse, ProverResult,
+    },
+    requests::ListJobsQuery,
+};
+use ed25519_dalek::VerifyingKey;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Bound,
+    path::PathBuf,
     sync::Arc,
-    time::{Duration, Instant},
+    time::Duration,
+};
+use tokio::sync::{watch, Mutex};
+use ulid::Ulid;
+
+use super::{
+    events::JobEvents,
+    job_storage::{JobStorage, JobStorageProvider},
+    workdir::{self, Retention},
+};
+use crate::{
+    auth::{auth_errors::AuthError, jwt::Claims},
+    errors::ProverError,
+    server::AppState,
+    threadpool::CairoVersionedInput,
 };
-use tokio::sync::Mutex;
 
-use crate::{auth::jwt::Claims, errors::ProverError, server::AppState};
+// Finished jobs are dropped this long after they finished so that the store doesn't grow forever
+const JOB_EXPIRY_SECS: u64 = 5 * 60 * 60; // 5 hours
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Job {
-    pub id: u64,
+    pub id: JobId,
     pub status: JobStatus,
     pub result: Option<String>,
-    pub created: Instant,
+    /// Creation time as a unix timestamp in seconds
+    pub created: u64,
+    /// Time the job completed, failed or was cancelled as a unix timestamp in seconds
+    #[serde(default)]
+    pub finished: Option<u64>,
+    /// Hex encoded public key of the submitter, empty for jobs created before owners were recorded
+    #[serde(default)]
+    pub owner: String,
+    /// Scheduling class the job was queued with
+    #[serde(default)]
+    pub priority: JobPriority,
+    #[serde(default)]
+    pub cairo_version: Option<CairoVersion>,
+    #[serde(default)]
+    pub layout: Option<String>,
+    /// Why the job failed, `None` for jobs that didn't fail in a worker
+    #[serde(default)]
+    pub error: Option<JobError>,
+    /// Work directory of the job's execution, `None` before it started and once deleted
+    #[serde(default)]
+    pub workdir: Option<PathBuf>,
+}
+
+impl Job {
+    /// Only the submitting key and admin keys may read or modify a job.
+    pub fn check_access(
+        &self,
+        claims: &Claims,
+        admin_keys: &[VerifyingKey],
+    ) -> Result<(), ProverError> {
+        if self.owner == claims.sub || claims.is_admin(admin_keys) {
+            Ok(())
+        } else {
+            Err(ProverError::Auth(AuthError::Forbidden))
+        }
+    }
+}
+
+/// Jobs submitted together to `POST /prove/batch`, expiring with them.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Batch {
+    pub id: BatchId,
+    /// Creation time as a unix timestamp in seconds
+    pub created: u64,
+    /// Hex encoded public key of the submitter
+    pub owner: String,
+    /// Job of each item, in submission order
+    pub jobs: Vec<JobId>,
+}
+
+impl Batch {
+    /// Only the submitting key and admin keys may read a batch.
+    pub fn check_access(
+        &self,
+        claims: &Claims,
+        admin_keys: &[VerifyingKey],
+    ) -> Result<(), ProverError> {
+        if self.owner == claims.sub || claims.is_admin(admin_keys) {
+            Ok(())
+        } else {
+            Err(ProverError::Auth(AuthError::Forbidden))
+        }
+    }
+}
+
+impl From<&Job> for JobSummary {
+    fn from(job: &Job) -> Self {
+        JobSummary {
+            id: job.id.clone(),
+            status: job.status.clone(),
+            created: job.created,
+            cairo_version: job.cairo_version.clone(),
+            layout: job.layout.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JobResponse {
     InProgress {
-        id: u64,
+        id: JobId,
         status: JobStatus,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        queue_position: Option<usize>,
     },
     Completed {
         result: ProverResult,
@@ -36,87 +137,628 @@ pub enum JobResponse {
     },
     Failed {
         error: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        details: Option<JobError>,
     },
 }
 
+/// Why the jobs attached to a discarded job failed.
+pub const LEADER_DISCARDED: &str = "The identical submission this job follows was rejected";
+
+/// A job created by [`JobStore::attach_or_create`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NewJob {
+    /// Follows the pending or running execution of an identical submission
+    Attached(JobId),
+    /// Starts a new execution, which still has to be queued
+    Created(JobId),
+}
+
 #[derive(Default, Clone)]
 pub struct JobStore {
     inner: Arc<Mutex<JobStoreInner>>,
+    storage: JobStorage,
+    retention: Retention,
 }
 
 impl JobStore {
-    pub async fn create_job(&self) -> u64 {
-        self.inner.lock().await.create_job()
+    /// Creates a store backed by `storage`, reloading every job it already holds.
+    pub async fn new(storage: JobStorage) -> Result<Self, ProverError> {
+        let mut inner = JobStoreInner::default();
+        for job in storage.load_jobs().await? {
+            inner.jobs.insert(job.id.clone(), job);
+        }
+        for batch in storage.load_batches().await? {
+            inner.batches.insert(batch.id.clone(), batch);
+        }
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+            storage,
+            retention: Retention::default(),
+        })
+    }
+    /// Keeps the work directories of finished jobs according to `retention`.
+    pub fn with_retention(self, retention: Retention) -> Self {
+        Self { retention, ..self }
+    }
+    /// Creates a pending job, persisting its input so it can be re-queued after a restart.
+    pub async fn create_job(
+        &self,
+        owner: &str,
+        priority: JobPriority,
+        input: &CairoVersionedInput,
+    ) -> Result<JobId, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let job = inner.create_job(owner, priority, input);
+        self.storage.save_input(&job.id, input).await?;
+        self.storage.save_job(&job).await?;
+        Ok(job.id)
+    }
+    /// Creates a job that is already completed with `result`, for proofs served from the cache.
+    pub async fn create_completed_job(
+        &self,
+        owner: &str,
+        priority: JobPriority,
+        input: &CairoVersionedInput,
+        result: String,
+    ) -> Result<JobId, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let id = inner.create_job(owner, priority, input).id;
+        if let Some(job) = inner.update_job_status(&id, JobStatus::Completed, Some(result)) {
+            self.storage.save_job(&job).await?;
+        }
+        Ok(id)
+    }
+    /// Attaches a new job to the pending or running execution of the submission with `key`, or
+    /// creates a job starting a new execution that identical submissions attach to from then on.
+    ///
+    /// Both happen under one lock, so identical submissions arriving together share a single
+    /// execution. `check_capacity` is only called before creating a job, an attached job takes
+    /// no room in the queue.
+    ///
+    /// The first event of the job is sent to `events` under the lock as well, `Queued` or the
+    /// phase of the running execution, so that no event of the execution precedes it.
+    pub async fn attach_or_create(
+        &self,
+        key: &str,
+        owner: &str,
+        priority: JobPriority,
+        input: &CairoVersionedInput,
+        events: &JobEvents,
+        check_capacity: impl FnOnce() -> Result<(), ProverError>,
+    ) -> Result<NewJob, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let leader = inner.in_flight.get(key).cloned().and_then(|leader| {
+            match inner.jobs.get(&leader).map(|job| &job.status) {
+                Some(status @ (JobStatus::Pending | JobStatus::Running)) => {
+                    Some((leader, status.clone()))
+                }
+                _ => None,
+            }
+        });
+        let Some((leader, status)) = leader else {
+            check_capacity()?;
+            let job = inner.create_job(owner, priority, input);
+            inner.in_flight.insert(key.to_string(), job.id.clone());
+            self.storage.save_input(&job.id, input).await?;
+            self.storage.save_job(&job).await?;
+            events.send([job.id.clone()], JobEvent::Queued);
+            return Ok(NewJob::Created(job.id));
+        };
+        let event = match status {
+            JobStatus::Running => events.phase(&leader).unwrap_or(JobEvent::RunningTrace),
+            _ => JobEvent::Queued,
+        };
+        let workdir = inner.jobs.get(&leader).and_then(|job| job.workdir.clone());
+        let id = inner.create_job(owner, priority, input).id;
+        let job = inner.update_job_status(&id, status, None).map(|mut job| {
+            job.workdir = workdir;
+            inner.jobs.insert(id.clone(), job.clone());
+            job
+        });
+        inner
+            .followers
+            .entry(leader.clone())
+            .or_default()
+            .push(id.clone());
+        if let Some(job) = job {
+            self.storage.save_job(&job).await?;
+        }
+        events.send([id.clone()], event);
+        drop(inner);
+        // Shares the input the leader saved instead of writing the program again
+        self.storage.link_input(&id, &leader).await?;
+        Ok(NewJob::Attached(id))
+    }
+    /// Records where the execution of `job_id` happens, for the jobs attached to it as well.
+    pub async fn set_workdir(&self, job_id: &JobId, workdir: PathBuf) -> Result<(), ProverError> {
+        let mut inner = self.inner.lock().await;
+        for id in inner.execution(job_id) {
+            if let Some(job) = inner.jobs.get_mut(&id) {
+                job.workdir = Some(workdir.clone());
+                let job = job.clone();
+                self.storage.save_job(&job).await?;
+            }
+        }
+        Ok(())
+    }
+    /// Deletes the work directories of finished jobs past their retention.
+    ///
+    /// Directories of pending or running jobs are kept, jobs attached to an execution share it.
+    pub async fn clean_workdirs(&self) -> Result<(), ProverError> {
+        let mut inner = self.inner.lock().await;
+        let in_use = inner.workdirs_in_use();
+        let mut expired = Vec::new();
+        for job in inner.jobs.values_mut() {
+            // Jobs stored before finish times were recorded count from their creation
+            let finished = job.finished.unwrap_or(job.created);
+            let age = Duration::from_secs(now().saturating_sub(finished));
+            if !is_finished(&job.status) || !self.retention.expired(&job.status, age) {
+                continue;
+            }
+            if let Some(dir) = job.workdir.take() {
+                self.storage.save_job(job).await?;
+                if !in_use.contains(&dir) {
+                    expired.push(dir);
+                }
+            }
+        }
+        // Deleting large traces takes a while, other requests shouldn't wait for it
+        drop(inner);
+        for dir in expired {
+            workdir::remove(&dir).await?;
+        }
+        Ok(())
+    }
+    /// Whether jobs attached to `job_id` still wait on its execution.
+    pub async fn has_followers(&self, job_id: &JobId) -> bool {
+        let inner = self.inner.lock().await;
+        inner
+            .followers
+            .get(job_id)
+            .is_some_and(|followers| !followers.is_empty())
+    }
+    /// The job running the execution `job_id` is attached to, `job_id` itself for a job running
+    /// its own.
+    pub async fn leader(&self, job_id: &JobId) -> JobId {
+        self.inner.lock().await.leader(job_id)
+    }
+    /// The job running the execution of `job_id`, then the jobs attached to it.
+    pub async fn execution(&self, job_id: &JobId) -> Vec<JobId> {
+        self.inner.lock().await.execution(job_id)
+    }
+    /// Updates the job and the jobs attached to it, returning the ids of the updated jobs.
+    pub async fn update_job_status(
+        &self,
+        job_id: &JobId,
+        status: JobStatus,
+        result: Option<String>,
+    ) -> Result<Vec<JobId>, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let mut updated = Vec::new();
+        for id in inner.execution(job_id) {
+            if let Some(job) = inner.update_job_status(&id, status.clone(), result.clone()) {
+                self.persist(&job).await?;
+                updated.push(id);
+            }
+        }
+        if is_finished(&status) {
+            inner.finish_execution(job_id);
+        }
+        Ok(updated)
+    }
+    /// Marks the job and the jobs attached to it as failed, keeping the structured error next
+    /// to the plain message. Returns the ids of the updated jobs.
+    pub async fn fail_job(
+        &self,
+        job_id: &JobId,
+        error: JobError,
+    ) -> Result<Vec<JobId>, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let mut updated = Vec::new();
+        for id in inner.execution(job_id) {
+            if let Some(job) = inner.fail_job(&id, error.clone()) {
+                self.persist(&job).await?;
+                updated.push(id);
+            }
+        }
+        inner.finish_execution(job_id);
+        Ok(updated)
+    }
+    /// Marks a pending or running job as cancelled and signals its cancellation receiver.
+    ///
+    /// An execution keeps going as long as a job attached to it waits on it. Returns `false`
+    /// when the job has already finished.
+    pub async fn cancel_job(&self, job_id: &JobId) -> Result<bool, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let Some(job) = inner.jobs.get_mut(job_id) else {
+            return Ok(false);
+        };
+        if !matches!(job.status, JobStatus::Pending | JobStatus::Running) {
+            return Ok(false);
+        }
+        job.status = JobStatus::Cancelled;
+        job.finished = Some(now());
+        let job = job.clone();
+        let leader = inner.followers.iter_mut().find_map(|(leader, followers)| {
+            let position = followers.iter().position(|id| id == job_id)?;
+            followers.remove(position);
+            Some(leader.clone())
+        });
+        for id in std::iter::once(job_id.clone()).chain(leader) {
+            if !inner.is_abandoned(&id) {
+                continue;
+            }
+            if let Some(sender) = inner.cancellations.remove(&id) {
+                sender.send_replace(true);
+            }
+            inner.finish_execution(&id);
+        }
+        self.persist(&job).await?;
+        Ok(true)
+    }
+    /// Returns a receiver that flips to `true` once the job gets cancelled.
+    pub async fn cancellation(&self, job_id: &JobId) -> watch::Receiver<bool> {
+        let mut inner = self.inner.lock().await;
+        let cancelled = inner.is_abandoned(job_id);
+        inner
+            .cancellations
+            .entry(job_id.clone())
+            .or_insert_with(|| watch::channel(cancelled).0)
+            .subscribe()
+    }
+    /// Groups the jobs of the items of a batch under a new batch id.
+    pub async fn create_batch(&self, owner: &str, jobs: Vec<JobId>) -> Result<Batch, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let batch = Batch {
+            id: BatchId::from(Ulid::new().to_string()),
+            created: now(),
+            owner: owner.to_string(),
+            jobs,
+        };
+        inner.batches.insert(batch.id.clone(), batch.clone());
+        self.storage.save_batch(&batch).await?;
+        Ok(batch)
+    }
+    pub async fn get_batch(&self, id: &BatchId) -> Option<Batch> {
+        self.inner.lock().await.batches.get(id).cloned()
+    }
+    /// Current status of every job of `batch`, `Unknown` for jobs that expired.
+    pub async fn batch_status(&self, batch: &Batch) -> BatchResponse {
+        let inner = self.inner.lock().await;
+        let jobs: Vec<BatchJob> = batch
+            .jobs
+            .iter()
+            .map(|id| BatchJob {
+                id: id.clone(),
+                status: inner
+                    .jobs
+                    .get(id)
+                    .map_or(JobStatus::Unknown, |job| job.status.clone()),
+            })
+            .collect();
+        let count = |status: JobStatus| jobs.iter().filter(|job| job.status == status).count();
+        BatchResponse {
+            id: batch.id.clone(),
+            created: batch.created,
+            pending: count(JobStatus::Pending),
+            running: count(JobStatus::Running),
+            completed: count(JobStatus::Completed),
+            failed: count(JobStatus::Failed),
+            cancelled: count(JobStatus::Cancelled),
+            expired: count(JobStatus::Unknown),
+            jobs,
+        }
     }
-    pub async fn update_job_status(&self, job_id: u64, status: JobStatus, result: Option<String>) {
-        self.inner
+    pub async fn get_job(&self, id: &JobId) -> Option<Job> {
+        self.inner.lock().await.jobs.get(id).cloned()
+    }
+    /// Forgets a job that was created but never queued, e.g. because the queue was full.
+    ///
+    /// Jobs that attached to it in the meantime fail, their ids are returned.
+    pub async fn discard_job(&self, job_id: &JobId) -> Result<Vec<JobId>, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let mut failed = Vec::new();
+        for id in inner.execution(job_id).into_iter().skip(1) {
+            let error = Some(LEADER_DISCARDED.to_string());
+            if let Some(job) = inner.update_job_status(&id, JobStatus::Failed, error) {
+                self.persist(&job).await?;
+                failed.push(id);
+            }
+        }
+        inner.finish_execution(job_id);
+        inner.jobs.remove(job_id);
+        inner.cancellations.remove(job_id);
+        self.storage.remove_job(job_id).await?;
+        Ok(failed)
+    }
+    /// Records the output of a finished runner or prover subprocess.
+    pub async fn append_log(&self, job_id: &JobId, log: &CommandLog) -> Result<(), ProverError> {
+        let mut inner = self.inner.lock().await;
+        let logs = inner.logs.entry(job_id.clone()).or_default();
+        logs.commands.push(log.clone());
+        let logs = logs.clone();
+        self.storage.save_logs(job_id, &logs).await
+    }
+    pub async fn logs(&self, job_id: &JobId) -> Result<JobLogs, ProverError> {
+        if let Some(logs) = self.inner.lock().await.logs.get(job_id) {
+            return Ok(logs.clone());
+        }
+        // Logs of jobs from before a restart are only on disk
+        Ok(self.storage.load_logs(job_id).await?.unwrap_or_default())
+    }
+    /// Lists jobs newest first, restricted to `owner` when given.
+    ///
+    /// The cursor is the id of the last job of the previous page, ids sort by creation time
+    /// so the next page continues right below it.
+    pub async fn list_jobs(&self, owner: Option<&str>, query: &ListJobsQuery) -> ListJobsResponse {
+        let inner = self.inner.lock().await;
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let upper = match &query.cursor {
+            Some(cursor) => Bound::Excluded(cursor.clone()),
+            None => Bound::Unbounded,
+        };
+        let mut jobs: Vec<JobSummary> = inner
+            .jobs
+            .range((Bound::Unbounded, upper))
+            .rev()
+            .map(|(_, job)| job)
+            .filter(|job| owner.map_or(true, |owner| job.owner == owner))
+            .filter(|job| query.status.as_ref().map_or(true, |s| job.status == *s))
+            .filter(|job| query.created_after.map_or(true, |t| job.created >= t))
+            .filter(|job| query.created_before.map_or(true, |t| job.created < t))
+            .take(limit + 1)
+            .map(JobSummary::from)
+            .collect();
+        let next_cursor = if jobs.len() > limit {
+            jobs.truncate(limit);
+            jobs.last().map(|job| job.id.clone())
+        } else {
+            None
+        };
+        ListJobsResponse { jobs, next_cursor }
+    }
+    /// Resolves the jobs that were unfinished when the server stopped.
+    ///
+    /// Pending jobs are always handed back to be queued again. Running jobs are re-queued
+    /// when `requeue_running` is set and marked as failed otherwise. Jobs whose input was not
+    /// persisted cannot be run again and are failed as well.
+    pub async fn recover(
+        &self,
+        requeue_running: bool,
+    ) -> Result<Vec<(Job, CairoVersionedInput)>, ProverError> {
+        let unfinished: Vec<Job> = self
+            .inner
             .lock()
             .await
-            .update_job_status(job_id, status, result)
+            .jobs
+            .values()
+            .filter(|job| matches!(job.status, JobStatus::Pending | JobStatus::Running))
+            .cloned()
+            .collect();
+        let mut requeued = Vec::new();
+        for job in unfinished {
+            let requeue = matches!(job.status, JobStatus::Pending) || requeue_running;
+            match self.storage.load_input(&job.id).await? {
+                Some(input) if requeue => {
+                    self.update_job_status(&job.id, JobStatus::Pending, None)
+                        .await?;
+                    requeued.push((job, input));
+                }
+                _ => {
+                    self.update_job_status(
+                        &job.id,
+                        JobStatus::Failed,
+                        Some("Job was interrupted by a server restart".to_string()),
+                    )
+                    .await?;
+                }
+            }
+        }
+        Ok(requeued)
     }
-    pub async fn get_job(&self, id: u64) -> Option<Job> {
-        self.inner.lock().await.get_job(id)
+    async fn persist(&self, job: &Job) -> Result<(), ProverError> {
+        if is_finished(&job.status) {
+            self.storage.remove_input(&job.id).await?;
+        }
+        self.storage.save_job(job).await
+    }
+    /// Forgets the batches and the finished jobs that expired, deleting what they left on disk.
+    ///
+    /// Called by the janitor rather than on every access, the files go once the lock is released.
+    pub async fn clear_old_jobs(&self) -> Result<(), ProverError> {
+        let mut inner = self.inner.lock().await;
+        let batches = inner.clear_old_batches();
+        // Expired jobs are finished, the directories of the others must survive them
+        let in_use = inner.workdirs_in_use();
+        let expired = inner.clear_old_jobs();
+        drop(inner);
+        for id in batches {
+            self.storage.remove_batch(&id).await?;
+        }
+        for job in expired {
+            self.storage.remove_job(&job.id).await?;
+            if let Some(dir) = job.workdir.filter(|dir| !in_use.contains(dir)) {
+                workdir::remove(&dir).await?;
+            }
+        }
+        Ok(())
     }
 }
 
 #[derive(Default)]
 struct JobStoreInner {
-    jobs: BTreeMap<u64, Job>,
-    counter: u64,
+    jobs: BTreeMap<JobId, Job>,
+    cancellations: HashMap<JobId, watch::Sender<bool>>,
+    logs: HashMap<JobId, JobLogs>,
+    // Queued jobs identical submissions attach to, by proof cache key
+    in_flight: HashMap<String, JobId>,
+    // Jobs attached to the execution of another job, they follow its status
+    followers: HashMap<JobId, Vec<JobId>>,
+    batches: HashMap<BatchId, Batch>,
 }
 
 impl JobStoreInner {
-    pub fn create_job(&mut self) -> u64 {
-        let job_id = self.counter;
-        self.counter += 1;
+    pub fn create_job(
+        &mut self,
+        owner: &str,
+        priority: JobPriority,
+        input: &CairoVersionedInput,
+    ) -> Job {
+        // ULIDs are unguessable and still sort by creation time
+        let job_id = JobId::from(Ulid::new().to_string());
         let new_job = Job {
-            id: job_id,
+            id: job_id.clone(),
             status: JobStatus::Pending,
             result: None,
-            created: Instant::now(),
-        };
-        self.jobs.insert(job_id, new_job);
-        self.clear_old_jobs();
-        job_id
-    }
-    pub fn update_job_status(&mut self, job_id: u64, status: JobStatus, result: Option<String>) {
-        if let Some(job) = self.jobs.get_mut(&job_id) {
-            job.status = status;
-            job.result = result;
-        }
-        self.clear_old_jobs()
-    }
-    pub fn get_job(&mut self, id: u64) -> Option<Job> {
-        let job = self.jobs.get(&id).cloned();
-        self.clear_old_jobs();
-        job
-    }
-    // Clear old jobs so that the memory doesn't go balistic if the server runs for a long time
-    fn clear_old_jobs(&mut self) {
-        let expiry_duration = Duration::from_secs(5 * 60 * 60); // 5 hours
-        while let Some((id, job)) = self.jobs.pop_first() {
-            if job.created.elapsed() < expiry_duration {
-                self.jobs.insert(id, job);
-                break;
-            }
+            created: now(),
+            finished: None,
+            owner: owner.to_string(),
+            priority,
+            cairo_version: Some(input.cairo_version()),
+            layout: Some(input.layout().to_string()),
+            error: None,
+            workdir: None,
+        };
+        self.jobs.insert(job_id, new_job.clone());
+        new_job
+    }
+    pub fn update_job_status(
+        &mut self,
+        job_id: &JobId,
+        status: JobStatus,
+        result: Option<String>,
+    ) -> Option<Job> {
+        let job = self.jobs.get_mut(job_id)?;
+        // A cancelled job keeps its status even if a worker reports on it afterwards
+        if matches!(job.status, JobStatus::Cancelled) {
+            return None;
+        }
+        job.finished = is_finished(&status).then(now);
+        job.status = status;
+        job.result = result;
+        job.error = None;
+        let job = job.clone();
+        if is_finished(&job.status) {
+            self.cancellations.remove(job_id);
+        }
+        Some(job)
+    }
+    pub fn fail_job(&mut self, job_id: &JobId, error: JobError) -> Option<Job> {
+        self.update_job_status(job_id, JobStatus::Failed, Some(error.to_string()))?;
+        let job = self.jobs.get_mut(job_id)?;
+        job.error = Some(error);
+        Some(job.clone())
+    }
+    // The job running the execution first, then the jobs attached to it
+    fn execution(&self, job_id: &JobId) -> Vec<JobId> {
+        let followers = self.followers.get(job_id).into_iter().flatten();
+        std::iter::once(job_id).chain(followers).cloned().collect()
+    }
+    fn leader(&self, job_id: &JobId) -> JobId {
+        self.followers
+            .iter()
+            .find(|(_, followers)| followers.contains(job_id))
+            .map_or_else(|| job_id.clone(), |(leader, _)| leader.clone())
+    }
+    fn finish_execution(&mut self, job_id: &JobId) {
+        self.followers.remove(job_id);
+        self.in_flight.retain(|_, leader| leader != job_id);
+    }
+    // A cancelled job's execution only stops once no attached job waits on it
+    fn is_abandoned(&self, job_id: &JobId) -> bool {
+        let cancelled = self
+            .jobs
+            .get(job_id)
+            .map_or(true, |job| matches!(job.status, JobStatus::Cancelled));
+        let followed = self
+            .followers
+            .get(job_id)
+            .is_some_and(|followers| !followers.is_empty());
+        cancelled && !followed
+    }
+    // Clear old jobs so that the memory doesn't go balistic if the server runs for a long time.
+    // Pending and running jobs stay however long they take, finished ones expire after they
+    // finished.
+    fn clear_old_jobs(&mut self) -> Vec<Job> {
+        let now = now();
+        // Ids sort by creation time and jobs finish after they were created, so the scan ends
+        // at the first job created too recently to have expired
+        let expired: Vec<JobId> = self
+            .jobs
+            .values()
+            .take_while(|job| now.saturating_sub(job.created) >= JOB_EXPIRY_SECS)
+            .filter(|job| {
+                // Jobs stored before finish times were recorded count from their creation
+                let finished = job.finished.unwrap_or(job.created);
+                is_finished(&job.status) && now.saturating_sub(finished) >= JOB_EXPIRY_SECS
+            })
+            .map(|job| job.id.clone())
+            .collect();
+        let mut jobs = Vec::with_capacity(expired.len());
+        for id in expired {
+            self.cancellations.remove(&id);
+            self.logs.remove(&id);
+            self.finish_execution(&id);
+            jobs.extend(self.jobs.remove(&id));
+        }
+        jobs
+    }
+    fn clear_old_batches(&mut self) -> Vec<BatchId> {
+        let expired: Vec<BatchId> = self
+            .batches
+            .values()
+            .filter(|batch| now().saturating_sub(batch.created) >= JOB_EXPIRY_SECS)
+            .map(|batch| batch.id.clone())
+            .collect();
+        for id in expired.iter() {
+            self.batches.remove(id);
         }
+        expired
+    }
+    fn workdirs_in_use(&self) -> HashSet<PathBuf> {
+        self.jobs
+            .values()
+            .filter(|job| !is_finished(&job.status))
+            .filter_map(|job| job.workdir.clone())
+            .collect()
     }
 }
 
+pub(crate) fn is_finished(status: &JobStatus) -> bool {
+    matches!(
+        status,
+        JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+    )
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
+
 pub async fn get_job(
-    Path(id): Path<u64>,
+    Path(id): Path<JobId>,
     State(app_state): State<AppState>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<impl IntoResponse, ProverError> {
-    if let Some(job) = app_state.job_store.get_job(id).await {
+    if let Some(job) = app_state.job_store.get_job(&id).await {
+        job.check_access(&claims, &app_state.admin_keys)?;
         let (status, response) = match job.status {
-            JobStatus::Pending | JobStatus::Running => (
-                StatusCode::OK,
-                Json(JobResponse::InProgress {
-                    id: job.id,
-                    status: job.status.clone(),
-                }),
-            ),
+            JobStatus::Pending | JobStatus::Running => {
+                // A job attached to an execution waits where the job running it does
+                let leader = app_state.job_store.leader(&job.id).await;
+                (
+                    StatusCode::OK,
+                    Json(JobResponse::InProgress {
+                        queue_position: app_state.thread_pool.queue_position(&leader),
+                        id: job.id,
+                        status: job.status.clone(),
+                    }),
+                )
+            }
             JobStatus::Completed => (
                 StatusCode::OK,
                 Json(JobResponse::Completed {
@@ -131,12 +773,21 @@ pub async fn get_job(
                         .result
                         .clone()
                         .unwrap_or_else(|| "Unknown error".to_string()),
+                    details: job.error,
+                }),
+            ),
+            JobStatus::Cancelled => (
+                StatusCode::GONE,
+                Json(JobResponse::Failed {
+                    error: ProverError::Cancelled.to_string(),
+                    details: None,
                 }),
             ),
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(JobResponse::Failed {
                     error: "Unknown error".to_string(),
+                    details: None,
                 }),
             ),
         };
@@ -145,3 +796,766 @@ pub async fn get_job(
         Err(ProverError::CustomError("Job not found".to_string()))
     }
 }
+pub async fn get_job_logs(
+    Path(id): Path<JobId>,
+    State(app_state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<JobLogs>, ProverError> {
+    let Some(job) = app_state.job_store.get_job(&id).await else {
+        return Err(ProverError::CustomError("Job not found".to_string()));
+    };
+    job.check_access(&claims, &app_state.admin_keys)?;
+    Ok(Json(app_state.job_store.logs(&id).await?))
+}
+/// Lists the caller's jobs, or every job when the caller holds an admin key.
+pub async fn list_jobs(
+    State(app_state): State<AppState>,
+    Query(query): Query<ListJobsQuery>,
+    claims: Claims,
+) -> Result<Json<ListJobsResponse>, ProverError> {
+    let owner = (!claims.is_admin(&app_state.admin_keys)).then_some(claims.sub.as_str());
+    Ok(Json(app_state.job_store.list_jobs(owner, &query).await))
+}
+/// Progress of a batch, for the submitting key and admin keys.
+pub async fn get_batch(
+    Path(id): Path<BatchId>,
+    State(app_state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<BatchResponse>, ProverError> {
+    let batch = app_state
+        .job_store
+        .get_batch(&id)
+        .await
+        .ok_or_else(|| ProverError::NotFound(format!("Batch {id}")))?;
+    batch.check_access(&claims, &app_state.admin_keys)?;
+    Ok(Json(app_state.job_store.batch_status(&batch).await))
+}
+pub async fn cancel_job(
+    Path(id): Path<JobId>,
+    State(app_state): State<AppState>,
+    claims: Claims,
+) -> Result<impl IntoResponse, ProverError> {
+    let Some(job) = app_state.job_store.get_job(&id).await else {
+        return Err(ProverError::CustomError("Job not found".to_string()));
+    };
+    job.check_access(&claims, &app_state.admin_keys)?;
+    if !cancel(&app_state, &id).await? {
+        return Err(ProverError::CustomError("Job already finished".to_string()));
+    }
+    Ok(())
+}
+
+/// Cancels a job and drops it from the queue, returns false when it already finished.
+pub(crate) async fn cancel(app_state: &AppState, id: &JobId) -> Result<bool, ProverError> {
+    if !app_state.job_store.cancel_job(id).await? {
+        return Ok(false);
+    }
+    // Jobs attached to the cancelled one still wait on its execution
+    if !app_state.job_store.has_followers(id).await {
+        app_state.thread_pool.remove(id);
+    }
+    app_state.events.send([id.clone()], JobEvent::Cancelled);
+    Ok(true)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::threadpool::{prove::ProvePaths, UploadedInput};
+    use crate::utils::{job_storage::FileJobStorage, workdir::RetentionPolicy};
+    use common::models::JobPhase;
+    use common::prover_input::{CairoCompiledProgram, CairoProverInput, SubmissionParams};
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+    use tempfile::tempdir;
+
+    const OWNER: &str = "0x01";
+
+    pub(crate) fn cairo_input() -> CairoVersionedInput {
+        CairoVersionedInput::Cairo(CairoProverInput {
+            program: CairoCompiledProgram {
+                type_declarations: serde_json::Value::Null,
+                libfunc_declarations: serde_json::Value::Null,
+                statements: serde_json::Value::Null,
+                funcs: serde_json::Value::Null,
+            },
+            program_input: vec![1.into(), 2.into()],
+            layout: "recursive".to_string(),
+            n_queries: Some(16),
+            pow_bits: Some(20),
+            priority: None,
+            prover_params: None,
+            prover_config: None,
+        })
+    }
+
+    async fn persistent_store(path: std::path::PathBuf) -> JobStore {
+        let storage = FileJobStorage::new(path).await.unwrap();
+        JobStore::new(JobStorage::Persistent(storage))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_jobs_survive_restart() {
+        let temp_dir = tempdir().unwrap();
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let first = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        let second = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        store
+            .update_job_status(&first, JobStatus::Completed, Some("proof".to_string()))
+            .await
+            .unwrap();
+
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let job = store.get_job(&first).await.unwrap();
+        assert!(matches!(job.status, JobStatus::Completed));
+        assert_eq!(job.result, Some("proof".to_string()));
+        assert!(matches!(
+            store.get_job(&second).await.unwrap().status,
+            JobStatus::Pending
+        ));
+
+        // New ids never collide with the reloaded ones
+        let third = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        assert!(third != first && third != second);
+    }
+
+    #[tokio::test]
+    async fn test_recover_fails_running_jobs() {
+        let temp_dir = tempdir().unwrap();
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let pending = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        let running = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        store
+            .update_job_status(&running, JobStatus::Running, None)
+            .await
+            .unwrap();
+
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let requeued = store.recover(false).await.unwrap();
+        assert_eq!(
+            requeued
+                .iter()
+                .map(|(job, _)| job.id.clone())
+                .collect::<Vec<_>>(),
+            vec![pending]
+        );
+        assert!(matches!(
+            store.get_job(&running).await.unwrap().status,
+            JobStatus::Failed
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_recover_requeues_running_jobs() {
+        let temp_dir = tempdir().unwrap();
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let running = store
+            .create_job(OWNER, JobPriority::High, &cairo_input())
+            .await
+            .unwrap();
+        store
+            .update_job_status(&running, JobStatus::Running, None)
+            .await
+            .unwrap();
+
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let requeued = store.recover(true).await.unwrap();
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].0.id, running);
+        assert_eq!(requeued[0].0.priority, JobPriority::High);
+        assert!(matches!(
+            store.get_job(&running).await.unwrap().status,
+            JobStatus::Pending
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_input_survives_restart() {
+        let temp_dir = tempdir().unwrap();
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let workdir = tempdir().unwrap();
+        let paths = ProvePaths::in_dir(workdir.path());
+        std::fs::write(&paths.program, "{}").unwrap();
+        std::fs::write(&paths.program_input, "[1]").unwrap();
+        let input = CairoVersionedInput::Upload(UploadedInput {
+            cairo_version: CairoVersion::Cairo,
+            program: paths.program,
+            program_input: paths.program_input,
+            params: SubmissionParams {
+                layout: "recursive".to_string(),
+                ..Default::default()
+            },
+            builtins: vec!["output".to_string()],
+            digest: "0x01".to_string(),
+        });
+        let job_id = store
+            .create_job(OWNER, JobPriority::Normal, &input)
+            .await
+            .unwrap();
+        // The request's work directory goes away with the server
+        drop(workdir);
+
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let requeued = store.recover(false).await.unwrap();
+        let CairoVersionedInput::Upload(input) = &requeued[0].1 else {
+            panic!("expected an uploaded input");
+        };
+        assert_eq!(std::fs::read_to_string(&input.program).unwrap(), "{}");
+        assert_eq!(
+            std::fs::read_to_string(&input.program_input).unwrap(),
+            "[1]"
+        );
+
+        // The files go with the input once the job finished
+        store
+            .update_job_status(&job_id, JobStatus::Completed, None)
+            .await
+            .unwrap();
+        assert!(!input.program.exists());
+        assert!(!input.program_input.exists());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_job() {
+        let store = JobStore::default();
+        let job_id = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        let cancel = store.cancellation(&job_id).await;
+        assert!(!*cancel.borrow());
+
+        assert!(store.cancel_job(&job_id).await.unwrap());
+        assert!(*cancel.borrow());
+
+        // A worker finishing afterwards doesn't override the cancellation
+        store
+            .update_job_status(&job_id, JobStatus::Completed, Some("proof".to_string()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.get_job(&job_id).await.unwrap().status,
+            JobStatus::Cancelled
+        ));
+        assert!(!store.cancel_job(&job_id).await.unwrap());
+    }
+
+    async fn attach_or_create(store: &JobStore, owner: &str) -> NewJob {
+        store
+            .attach_or_create(
+                "key",
+                owner,
+                JobPriority::Normal,
+                &cairo_input(),
+                &JobEvents::default(),
+                || Ok(()),
+            )
+            .await
+            .unwrap()
+    }
+
+    // Submits to `store` and returns the new job along with the events sent for it
+    async fn submitted_events(store: &JobStore, events: &JobEvents) -> (JobId, Vec<JobEvent>) {
+        let (_, last_id) = events.subscribe();
+        let (NewJob::Created(id) | NewJob::Attached(id)) = store
+            .attach_or_create(
+                "key",
+                OWNER,
+                JobPriority::Normal,
+                &cairo_input(),
+                events,
+                || Ok(()),
+            )
+            .await
+            .unwrap();
+        let sent = events.since(last_id, u64::MAX).unwrap();
+        assert!(sent.iter().all(|message| message.job_id == id));
+        (
+            id,
+            sent.iter().map(|message| message.event.clone()).collect(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_new_jobs_start_with_the_execution_state() {
+        let store = JobStore::default();
+        let events = JobEvents::default();
+        let (leader, sent) = submitted_events(&store, &events).await;
+        assert!(matches!(sent[..], [JobEvent::Queued]));
+        let (_, sent) = submitted_events(&store, &events).await;
+        assert!(matches!(sent[..], [JobEvent::Queued]));
+
+        let running = store
+            .update_job_status(&leader, JobStatus::Running, None)
+            .await
+            .unwrap();
+        events.send(running, JobEvent::RunningProve);
+        let (_, sent) = submitted_events(&store, &events).await;
+        assert!(matches!(sent[..], [JobEvent::RunningProve]));
+    }
+
+    #[tokio::test]
+    async fn test_attach_to_execution() {
+        let store = JobStore::default();
+        let NewJob::Created(leader) = attach_or_create(&store, OWNER).await else {
+            panic!("Expected a new execution");
+        };
+        store
+            .update_job_status(&leader, JobStatus::Running, None)
+            .await
+            .unwrap();
+        let NewJob::Attached(first) = attach_or_create(&store, "0x02").await else {
+            panic!("Expected to attach to the execution");
+        };
+        let NewJob::Attached(second) = attach_or_create(&store, "0x02").await else {
+            panic!("Expected to attach to the execution");
+        };
+        assert_ne!(first, second);
+        assert!(matches!(
+            store.get_job(&first).await.unwrap().status,
+            JobStatus::Running
+        ));
+
+        // Cancelling the leader doesn't stop the execution the others wait on
+        let cancel = store.cancellation(&leader).await;
+        store.cancel_job(&first).await.unwrap();
+        store.cancel_job(&leader).await.unwrap();
+        assert!(!*cancel.borrow());
+        assert!(store.has_followers(&leader).await);
+
+        let updated = store
+            .update_job_status(&leader, JobStatus::Completed, Some("proof".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(updated, vec![second.clone()]);
+        let job = store.get_job(&second).await.unwrap();
+        assert!(matches!(job.status, JobStatus::Completed));
+        assert_eq!(job.result.as_deref(), Some("proof"));
+        assert!(matches!(
+            store.get_job(&first).await.unwrap().status,
+            JobStatus::Cancelled
+        ));
+        // Finished executions can't be attached to
+        assert!(matches!(
+            attach_or_create(&store, OWNER).await,
+            NewJob::Created(_)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_attach_or_create_is_atomic() {
+        let store = JobStore::default();
+        let submissions = (0..16).map(|_| {
+            let store = store.clone();
+            tokio::spawn(async move { attach_or_create(&store, OWNER).await })
+        });
+        let mut created = Vec::new();
+        let mut attached = Vec::new();
+        for submission in submissions.collect::<Vec<_>>() {
+            match submission.await.unwrap() {
+                NewJob::Created(id) => created.push(id),
+                NewJob::Attached(id) => attached.push(id),
+            }
+        }
+        assert_eq!(created.len(), 1);
+        let mut followers = store.execution(&created[0]).await.split_off(1);
+        followers.sort();
+        attached.sort();
+        assert_eq!(followers, attached);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_creates_no_job() {
+        let store = JobStore::default();
+        let result = store
+            .attach_or_create(
+                "key",
+                OWNER,
+                JobPriority::Normal,
+                &cairo_input(),
+                &JobEvents::default(),
+                || Err(ProverError::QueueFull(1)),
+            )
+            .await;
+        assert!(matches!(result, Err(ProverError::QueueFull(_))));
+        assert!(matches!(
+            attach_or_create(&store, OWNER).await,
+            NewJob::Created(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_discarded_execution_fails_followers() {
+        let store = JobStore::default();
+        let NewJob::Created(leader) = attach_or_create(&store, OWNER).await else {
+            panic!("Expected a new execution");
+        };
+        let NewJob::Attached(follower) = attach_or_create(&store, OWNER).await else {
+            panic!("Expected to attach to the execution");
+        };
+        assert_eq!(
+            store.discard_job(&leader).await.unwrap(),
+            vec![follower.clone()]
+        );
+        assert!(store.get_job(&leader).await.is_none());
+        let job = store.get_job(&follower).await.unwrap();
+        assert!(matches!(job.status, JobStatus::Failed));
+        assert_eq!(job.result.as_deref(), Some(LEADER_DISCARDED));
+        // The key no longer points at the discarded job
+        assert!(matches!(
+            attach_or_create(&store, OWNER).await,
+            NewJob::Created(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_execution_is_cancelled() {
+        let store = JobStore::default();
+        let NewJob::Created(leader) = attach_or_create(&store, OWNER).await else {
+            panic!("Expected a new execution");
+        };
+        let NewJob::Attached(follower) = attach_or_create(&store, OWNER).await else {
+            panic!("Expected to attach to the execution");
+        };
+        let cancel = store.cancellation(&leader).await;
+        store.cancel_job(&leader).await.unwrap();
+        assert!(!*cancel.borrow());
+        store.cancel_job(&follower).await.unwrap();
+        assert!(*cancel.borrow());
+    }
+
+    #[tokio::test]
+    async fn test_clean_workdirs() {
+        let store = JobStore::default();
+        let mut workdirs = Vec::new();
+        for status in [JobStatus::Completed, JobStatus::Failed, JobStatus::Running] {
+            let job_id = store
+                .create_job(OWNER, JobPriority::Normal, &cairo_input())
+                .await
+                .unwrap();
+            let dir = tempdir().unwrap().into_path();
+            store.set_workdir(&job_id, dir.clone()).await.unwrap();
+            store
+                .update_job_status(&job_id, status, None)
+                .await
+                .unwrap();
+            workdirs.push((job_id, dir));
+        }
+        store.clean_workdirs().await.unwrap();
+
+        // Only the failed job's directory is kept by default, running jobs are never touched
+        let kept: Vec<bool> = workdirs.iter().map(|(_, dir)| dir.exists()).collect();
+        assert_eq!(kept, [false, true, true]);
+        let completed = store.get_job(&workdirs[0].0).await.unwrap();
+        assert!(completed.workdir.is_none());
+        for (_, dir) in &workdirs[1..] {
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_finished_jobs_expire() {
+        let store = JobStore::default();
+        let mut jobs = Vec::new();
+        for _ in 0..3 {
+            let job_id = store
+                .create_job(OWNER, JobPriority::Normal, &cairo_input())
+                .await
+                .unwrap();
+            let dir = tempdir().unwrap().into_path();
+            store.set_workdir(&job_id, dir.clone()).await.unwrap();
+            store
+                .inner
+                .lock()
+                .await
+                .jobs
+                .get_mut(&job_id)
+                .unwrap()
+                .created = now() - 2 * JOB_EXPIRY_SECS;
+            jobs.push((job_id, dir));
+        }
+        // Running for longer than the expiry, finished recently and finished long ago
+        store
+            .update_job_status(&jobs[0].0, JobStatus::Running, None)
+            .await
+            .unwrap();
+        for (job_id, _) in &jobs[1..] {
+            store
+                .update_job_status(job_id, JobStatus::Completed, None)
+                .await
+                .unwrap();
+        }
+        store
+            .inner
+            .lock()
+            .await
+            .jobs
+            .get_mut(&jobs[2].0)
+            .unwrap()
+            .finished = Some(now() - JOB_EXPIRY_SECS);
+
+        store.clear_old_jobs().await.unwrap();
+        assert!(store.get_job(&jobs[0].0).await.is_some());
+        assert!(jobs[0].1.exists());
+        assert!(store.get_job(&jobs[1].0).await.is_some());
+        assert!(store.get_job(&jobs[2].0).await.is_none());
+        assert!(!jobs[2].1.exists());
+    }
+
+    #[tokio::test]
+    async fn test_workdir_retention_counts_from_finish() {
+        let store = JobStore::default().with_retention(Retention {
+            policy: RetentionPolicy::Keep,
+            keep_for: Duration::from_secs(60 * 60),
+        });
+        let job_id = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        let dir = tempdir().unwrap().into_path();
+        store.set_workdir(&job_id, dir.clone()).await.unwrap();
+        // Queued for longer than the retention period before finishing
+        store
+            .inner
+            .lock()
+            .await
+            .jobs
+            .get_mut(&job_id)
+            .unwrap()
+            .created = now() - 2 * 60 * 60;
+        store
+            .update_job_status(&job_id, JobStatus::Completed, None)
+            .await
+            .unwrap();
+        store.clean_workdirs().await.unwrap();
+        assert!(dir.exists());
+
+        store
+            .inner
+            .lock()
+            .await
+            .jobs
+            .get_mut(&job_id)
+            .unwrap()
+            .finished = Some(now() - 2 * 60 * 60);
+        store.clean_workdirs().await.unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_batch_status() {
+        let temp_dir = tempdir().unwrap();
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let mut jobs = Vec::new();
+        for _ in 0..3 {
+            let id = store
+                .create_job(OWNER, JobPriority::Normal, &cairo_input())
+                .await
+                .unwrap();
+            jobs.push(id);
+        }
+        store
+            .update_job_status(&jobs[0], JobStatus::Completed, Some("proof".to_string()))
+            .await
+            .unwrap();
+        store
+            .update_job_status(&jobs[1], JobStatus::Running, None)
+            .await
+            .unwrap();
+        let batch = store.create_batch(OWNER, jobs.clone()).await.unwrap();
+
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let batch = store.get_batch(&batch.id).await.unwrap();
+        let status = store.batch_status(&batch).await;
+        let ids: Vec<JobId> = status.jobs.iter().map(|job| job.id.clone()).collect();
+        assert_eq!(ids, jobs);
+        assert_eq!(
+            (status.pending, status.running, status.completed),
+            (1, 1, 1)
+        );
+        assert!(!status.is_finished());
+
+        // A job forgotten before the batch counts as finished
+        store
+            .update_job_status(&jobs[1], JobStatus::Failed, None)
+            .await
+            .unwrap();
+        assert!(store.cancel_job(&jobs[2]).await.unwrap());
+        let mut inner = store.inner.lock().await;
+        for job in inner.jobs.values_mut() {
+            job.created = now() - JOB_EXPIRY_SECS;
+        }
+        inner.jobs.get_mut(&jobs[2]).unwrap().finished = Some(now() - JOB_EXPIRY_SECS);
+        drop(inner);
+        store.clear_old_jobs().await.unwrap();
+        assert!(store.get_job(&jobs[2]).await.is_none());
+        let status = store.batch_status(&batch).await;
+        assert_eq!(status.jobs[2].status, JobStatus::Unknown);
+        assert_eq!((status.completed, status.failed, status.expired), (1, 1, 1));
+        assert!(status.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_job_logs_survive_restart() {
+        let temp_dir = tempdir().unwrap();
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let job_id = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        let log = CommandLog {
+            command: "cairo1-run".to_string(),
+            exit_code: Some(1),
+            stdout: String::new(),
+            stderr: "error: out of gas".to_string(),
+            truncated: false,
+            killed: None,
+        };
+        store.append_log(&job_id, &log).await.unwrap();
+        assert_eq!(store.logs(&job_id).await.unwrap().commands.len(), 1);
+
+        let store = persistent_store(temp_dir.path().to_path_buf()).await;
+        let logs = store.logs(&job_id).await.unwrap();
+        assert_eq!(logs.commands[0].stderr, "error: out of gas");
+    }
+
+    #[tokio::test]
+    async fn test_fail_job() {
+        let store = JobStore::default();
+        let job_id = store
+            .create_job(OWNER, JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        let error = JobError {
+            phase: JobPhase::Run,
+            message: "out of gas".to_string(),
+            exit_code: Some(1),
+            reason: None,
+        };
+        store.fail_job(&job_id, error).await.unwrap();
+
+        let job = store.get_job(&job_id).await.unwrap();
+        assert!(matches!(job.status, JobStatus::Failed));
+        assert_eq!(job.result.as_deref(), Some("run failed: out of gas"));
+        let error = job.error.unwrap();
+        assert_eq!(error.phase, JobPhase::Run);
+        assert_eq!(error.exit_code, Some(1));
+
+        // Re-queueing clears the previous failure
+        store
+            .update_job_status(&job_id, JobStatus::Pending, None)
+            .await
+            .unwrap();
+        assert!(store.get_job(&job_id).await.unwrap().error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_jobs() {
+        let store = JobStore::default();
+        let mut ids = Vec::new();
+        for _ in 0..5 {
+            ids.push(
+                store
+                    .create_job(OWNER, JobPriority::Normal, &cairo_input())
+                    .await
+                    .unwrap(),
+            );
+        }
+        store
+            .create_job("0x02", JobPriority::Normal, &cairo_input())
+            .await
+            .unwrap();
+        store
+            .update_job_status(&ids[1], JobStatus::Completed, None)
+            .await
+            .unwrap();
+        ids.sort();
+
+        let query = ListJobsQuery {
+            limit: Some(3),
+            ..Default::default()
+        };
+        let first = store.list_jobs(Some(OWNER), &query).await;
+        assert_eq!(first.jobs.len(), 3);
+        assert!(first.jobs.windows(2).all(|w| w[0].id > w[1].id));
+        assert_eq!(first.jobs[0].cairo_version, Some(CairoVersion::Cairo));
+        assert_eq!(first.jobs[0].layout.as_deref(), Some("recursive"));
+
+        let query = ListJobsQuery {
+            cursor: first.next_cursor,
+            ..query
+        };
+        let second = store.list_jobs(Some(OWNER), &query).await;
+        assert_eq!(second.jobs.len(), 2);
+        assert!(second.next_cursor.is_none());
+        let mut listed: Vec<JobId> = first
+            .jobs
+            .iter()
+            .chain(second.jobs.iter())
+            .map(|job| job.id.clone())
+            .collect();
+        listed.sort();
+        assert_eq!(listed, ids);
+
+        let query = ListJobsQuery {
+            status: Some(JobStatus::Completed),
+            ..Default::default()
+        };
+        assert_eq!(store.list_jobs(Some(OWNER), &query).await.jobs.len(), 1);
+        let query = ListJobsQuery {
+            created_after: Some(now() + 60),
+            ..Default::default()
+        };
+        assert!(store.list_jobs(Some(OWNER), &query).await.jobs.is_empty());
+        // Without an owner restriction every job is listed
+        let all = store.list_jobs(None, &ListJobsQuery::default()).await;
+        assert_eq!(all.jobs.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_job_access() {
+        let admin_key = SigningKey::generate(&mut OsRng).verifying_key();
+        let claims = |key: VerifyingKey| Claims {
+            sub: prefix_hex::encode(key.to_bytes()),
+            exp: 0,
+            session_key: key,
+        };
+        let owner_key = SigningKey::generate(&mut OsRng).verifying_key();
+        let other_key = SigningKey::generate(&mut OsRng).verifying_key();
+
+        let store = JobStore::default();
+        let job_id = store
+            .create_job(
+                &prefix_hex::encode(owner_key.to_bytes()),
+                JobPriority::Normal,
+                &cairo_input(),
+            )
+            .await
+            .unwrap();
+        let job = store.get_job(&job_id).await.unwrap();
+
+        assert!(job.check_access(&claims(owner_key), &[admin_key]).is_ok());
+        assert!(job.check_access(&claims(admin_key), &[admin_key]).is_ok());
+        assert!(matches!(
+            job.check_access(&claims(other_key), &[admin_key]),
+            Err(ProverError::Auth(AuthError::Forbidden))
+        ));
+    }
+}
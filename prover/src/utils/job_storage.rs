@@ -0,0 +1,408 @@
+use super::{
+    job::{Batch, Job},
+    programs::StoredProgram,
+};
+use crate::{
+    errors::ProverError,
+    threadpool::{CairoVersionedInput, UploadedInput},
+};
+use common::{
+    models::{BatchId, JobId, JobLogs},
+    prover_input::CompiledProgram,
+};
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use ulid::Ulid;
+
+const JOBS_DIR: &str = "jobs";
+const INPUTS_DIR: &str = "inputs";
+const LOGS_DIR: &str = "logs";
+const PROGRAMS_DIR: &str = "programs";
+const BATCHES_DIR: &str = "batches";
+
+pub(crate) trait JobStorageProvider {
+    async fn load_jobs(&self) -> Result<Vec<Job>, ProverError>;
+
+    async fn save_job(&self, job: &Job) -> Result<(), ProverError>;
+
+    async fn remove_job(&self, id: &JobId) -> Result<(), ProverError>;
+
+    async fn save_input(&self, id: &JobId, input: &CairoVersionedInput) -> Result<(), ProverError>;
+
+    async fn load_input(&self, id: &JobId) -> Result<Option<CairoVersionedInput>, ProverError>;
+
+    async fn remove_input(&self, id: &JobId) -> Result<(), ProverError>;
+
+    async fn link_input(&self, id: &JobId, from: &JobId) -> Result<(), ProverError>;
+
+    async fn save_logs(&self, id: &JobId, logs: &JobLogs) -> Result<(), ProverError>;
+
+    async fn load_logs(&self, id: &JobId) -> Result<Option<JobLogs>, ProverError>;
+
+    async fn load_programs(&self) -> Result<Vec<StoredProgram>, ProverError>;
+
+    async fn save_program(&self, program: &StoredProgram) -> Result<(), ProverError>;
+
+    async fn remove_program(&self, hash: &str) -> Result<(), ProverError>;
+
+    async fn save_program_body(&self, hash: &str, contents: &[u8]) -> Result<(), ProverError>;
+
+    async fn load_program_body(&self, hash: &str) -> Result<Option<CompiledProgram>, ProverError>;
+
+    async fn remove_program_body(&self, hash: &str) -> Result<(), ProverError>;
+
+    async fn load_batches(&self) -> Result<Vec<Batch>, ProverError>;
+
+    async fn save_batch(&self, batch: &Batch) -> Result<(), ProverError>;
+
+    async fn remove_batch(&self, id: &BatchId) -> Result<(), ProverError>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum JobStorage {
+    #[default]
+    Memory,
+    Persistent(FileJobStorage),
+}
+
+impl JobStorageProvider for JobStorage {
+    async fn load_jobs(&self) -> Result<Vec<Job>, ProverError> {
+        match self {
+            JobStorage::Memory => Ok(Vec::new()),
+            JobStorage::Persistent(storage) => storage.load_jobs().await,
+        }
+    }
+
+    async fn save_job(&self, job: &Job) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.save_job(job).await,
+        }
+    }
+
+    async fn remove_job(&self, id: &JobId) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.remove_job(id).await,
+        }
+    }
+
+    async fn save_input(&self, id: &JobId, input: &CairoVersionedInput) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.save_input(id, input).await,
+        }
+    }
+
+    async fn load_input(&self, id: &JobId) -> Result<Option<CairoVersionedInput>, ProverError> {
+        match self {
+            JobStorage::Memory => Ok(None),
+            JobStorage::Persistent(storage) => storage.load_input(id).await,
+        }
+    }
+
+    async fn remove_input(&self, id: &JobId) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.remove_input(id).await,
+        }
+    }
+
+    async fn link_input(&self, id: &JobId, from: &JobId) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.link_input(id, from).await,
+        }
+    }
+
+    async fn save_logs(&self, id: &JobId, logs: &JobLogs) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.save_logs(id, logs).await,
+        }
+    }
+
+    async fn load_logs(&self, id: &JobId) -> Result<Option<JobLogs>, ProverError> {
+        match self {
+            JobStorage::Memory => Ok(None),
+            JobStorage::Persistent(storage) => storage.load_logs(id).await,
+        }
+    }
+
+    async fn load_programs(&self) -> Result<Vec<StoredProgram>, ProverError> {
+        match self {
+            JobStorage::Memory => Ok(Vec::new()),
+            JobStorage::Persistent(storage) => storage.load_programs().await,
+        }
+    }
+
+    async fn save_program(&self, program: &StoredProgram) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.save_program(program).await,
+        }
+    }
+
+    async fn remove_program(&self, hash: &str) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.remove_program(hash).await,
+        }
+    }
+
+    async fn save_program_body(&self, hash: &str, contents: &[u8]) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.save_program_body(hash, contents).await,
+        }
+    }
+
+    async fn load_program_body(&self, hash: &str) -> Result<Option<CompiledProgram>, ProverError> {
+        match self {
+            JobStorage::Memory => Ok(None),
+            JobStorage::Persistent(storage) => storage.load_program_body(hash).await,
+        }
+    }
+
+    async fn remove_program_body(&self, hash: &str) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.remove_program_body(hash).await,
+        }
+    }
+
+    async fn load_batches(&self) -> Result<Vec<Batch>, ProverError> {
+        match self {
+            JobStorage::Memory => Ok(Vec::new()),
+            JobStorage::Persistent(storage) => storage.load_batches().await,
+        }
+    }
+
+    async fn save_batch(&self, batch: &Batch) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.save_batch(batch).await,
+        }
+    }
+
+    async fn remove_batch(&self, id: &BatchId) -> Result<(), ProverError> {
+        match self {
+            JobStorage::Memory => Ok(()),
+            JobStorage::Persistent(storage) => storage.remove_batch(id).await,
+        }
+    }
+}
+
+/// Stores every job as a JSON file in `<path>/jobs` and the input of unfinished jobs in
+/// `<path>/inputs`, so both can be reloaded after a restart. Subprocess logs go to `<path>/logs`
+/// registered programs to `<path>/programs`, their bodies in `.program` files next to the
+/// summaries, and batches to `<path>/batches`.
+///
+/// The files of an uploaded input are linked into `<path>/inputs` next to it, they would
+/// otherwise go away with the work directory of the request.
+#[derive(Debug, Clone)]
+pub struct FileJobStorage(PathBuf);
+
+impl FileJobStorage {
+    pub async fn new(path: PathBuf) -> Result<Self, ProverError> {
+        fs::create_dir_all(path.join(JOBS_DIR)).await?;
+        fs::create_dir_all(path.join(INPUTS_DIR)).await?;
+        fs::create_dir_all(path.join(LOGS_DIR)).await?;
+        fs::create_dir_all(path.join(PROGRAMS_DIR)).await?;
+        fs::create_dir_all(path.join(BATCHES_DIR)).await?;
+        Ok(Self(path))
+    }
+    fn job_file(&self, id: &JobId) -> PathBuf {
+        self.0.join(JOBS_DIR).join(format!("{id}.json"))
+    }
+    fn input_file(&self, id: &JobId) -> PathBuf {
+        self.0.join(INPUTS_DIR).join(format!("{id}.json"))
+    }
+    fn upload_files(&self, id: &JobId) -> [PathBuf; 2] {
+        let inputs = self.0.join(INPUTS_DIR);
+        [
+            inputs.join(format!("{id}.program")),
+            inputs.join(format!("{id}.program_input")),
+        ]
+    }
+    fn logs_file(&self, id: &JobId) -> PathBuf {
+        self.0.join(LOGS_DIR).join(format!("{id}.json"))
+    }
+    fn program_file(&self, hash: &str) -> PathBuf {
+        self.0.join(PROGRAMS_DIR).join(format!("{hash}.json"))
+    }
+    fn program_body_file(&self, hash: &str) -> PathBuf {
+        self.0.join(PROGRAMS_DIR).join(format!("{hash}.program"))
+    }
+    fn batch_file(&self, id: &BatchId) -> PathBuf {
+        self.0.join(BATCHES_DIR).join(format!("{id}.json"))
+    }
+}
+
+impl JobStorageProvider for FileJobStorage {
+    async fn load_jobs(&self) -> Result<Vec<Job>, ProverError> {
+        read_all(&self.0.join(JOBS_DIR), "job").await
+    }
+
+    async fn save_job(&self, job: &Job) -> Result<(), ProverError> {
+        write_atomically(&self.job_file(&job.id), &serde_json::to_vec(job)?).await
+    }
+
+    async fn remove_job(&self, id: &JobId) -> Result<(), ProverError> {
+        remove_if_exists(&self.job_file(id)).await?;
+        self.remove_input(id).await?;
+        remove_if_exists(&self.logs_file(id)).await
+    }
+
+    async fn save_input(&self, id: &JobId, input: &CairoVersionedInput) -> Result<(), ProverError> {
+        let CairoVersionedInput::Upload(upload) = input else {
+            return write_atomically(&self.input_file(id), &serde_json::to_vec(input)?).await;
+        };
+        let [program, program_input] = self.upload_files(id);
+        link_or_copy(&upload.program, &program).await?;
+        link_or_copy(&upload.program_input, &program_input).await?;
+        let input = CairoVersionedInput::Upload(UploadedInput {
+            program,
+            program_input,
+            ..upload.clone()
+        });
+        write_atomically(&self.input_file(id), &serde_json::to_vec(&input)?).await
+    }
+
+    async fn load_input(&self, id: &JobId) -> Result<Option<CairoVersionedInput>, ProverError> {
+        let input = read_if_exists(&self.input_file(id)).await?;
+        // An input linked from another job names the files of that job
+        Ok(input.map(|input| match input {
+            CairoVersionedInput::Upload(upload) => {
+                let [program, program_input] = self.upload_files(id);
+                CairoVersionedInput::Upload(UploadedInput {
+                    program,
+                    program_input,
+                    ..upload
+                })
+            }
+            input => input,
+        }))
+    }
+
+    async fn remove_input(&self, id: &JobId) -> Result<(), ProverError> {
+        for file in self.upload_files(id) {
+            remove_if_exists(&file).await?;
+        }
+        remove_if_exists(&self.input_file(id)).await
+    }
+
+    // Links the files rather than writing them again, a `from` input already removed is skipped
+    async fn link_input(&self, id: &JobId, from: &JobId) -> Result<(), ProverError> {
+        let files = self
+            .upload_files(from)
+            .into_iter()
+            .zip(self.upload_files(id));
+        for (from, to) in files.chain([(self.input_file(from), self.input_file(id))]) {
+            if fs::try_exists(&from).await? {
+                link_or_copy(&from, &to).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn save_logs(&self, id: &JobId, logs: &JobLogs) -> Result<(), ProverError> {
+        write_atomically(&self.logs_file(id), &serde_json::to_vec(logs)?).await
+    }
+
+    async fn load_logs(&self, id: &JobId) -> Result<Option<JobLogs>, ProverError> {
+        read_if_exists(&self.logs_file(id)).await
+    }
+
+    async fn load_programs(&self) -> Result<Vec<StoredProgram>, ProverError> {
+        read_all(&self.0.join(PROGRAMS_DIR), "program").await
+    }
+
+    async fn save_program(&self, program: &StoredProgram) -> Result<(), ProverError> {
+        write_atomically(
+            &self.program_file(&program.hash),
+            &serde_json::to_vec(program)?,
+        )
+        .await
+    }
+
+    async fn remove_program(&self, hash: &str) -> Result<(), ProverError> {
+        remove_if_exists(&self.program_file(hash)).await
+    }
+
+    async fn save_program_body(&self, hash: &str, contents: &[u8]) -> Result<(), ProverError> {
+        write_atomically(&self.program_body_file(hash), contents).await
+    }
+
+    async fn load_program_body(&self, hash: &str) -> Result<Option<CompiledProgram>, ProverError> {
+        read_if_exists(&self.program_body_file(hash)).await
+    }
+
+    async fn remove_program_body(&self, hash: &str) -> Result<(), ProverError> {
+        remove_if_exists(&self.program_body_file(hash)).await
+    }
+
+    async fn load_batches(&self) -> Result<Vec<Batch>, ProverError> {
+        read_all(&self.0.join(BATCHES_DIR), "batch").await
+    }
+
+    async fn save_batch(&self, batch: &Batch) -> Result<(), ProverError> {
+        write_atomically(&self.batch_file(&batch.id), &serde_json::to_vec(batch)?).await
+    }
+
+    async fn remove_batch(&self, id: &BatchId) -> Result<(), ProverError> {
+        remove_if_exists(&self.batch_file(id)).await
+    }
+}
+
+// Reads every JSON file of `dir`, skipping the ones that don't parse as a `kind`
+async fn read_all<T: DeserializeOwned>(dir: &Path, kind: &str) -> Result<Vec<T>, ProverError> {
+    let mut items = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let contents = fs::read(&path).await?;
+        match serde_json::from_slice::<T>(&contents) {
+            Ok(item) => items.push(item),
+            Err(e) => tracing::warn!("Skipping unreadable {} file {:?}: {}", kind, path, e),
+        }
+    }
+    Ok(items)
+}
+
+// Write to a temporary file first so a crash mid-write never leaves a truncated job behind.
+// Concurrent writes of the same file each get their own temporary file.
+async fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), ProverError> {
+    let tmp = path.with_extension(format!("{}.tmp", Ulid::new()));
+    fs::write(&tmp, contents).await?;
+    fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+async fn read_if_exists<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, ProverError> {
+    match fs::read(path).await {
+        Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// A hard link takes no space, a copy is only made when `to` is on another file system
+pub(crate) async fn link_or_copy(from: &Path, to: &Path) -> Result<(), ProverError> {
+    remove_if_exists(to).await?;
+    if fs::hard_link(from, to).await.is_err() {
+        fs::copy(from, to).await?;
+    }
+    Ok(())
+}
+
+async fn remove_if_exists(path: &Path) -> Result<(), ProverError> {
+    match fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,377 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::{errors::ProverError, threadpool::CairoVersionedInput};
+
+use super::public_input::PublicInput;
+
+/// Layout name that lets the server pick the layout.
+pub const AUTO: &str = "auto";
+
+struct Layout {
+    name: &'static str,
+    // Builtin name and the number of steps per builtin instance
+    builtins: &'static [(&'static str, u64)],
+    // Whether `auto` may pick the layout, the others are only used when requested by name
+    auto: bool,
+}
+
+// Layouts the runners and Stone support, with fewer and cheaper columns first
+const LAYOUTS: [Layout; 11] = [
+    Layout {
+        name: "plain",
+        builtins: &[],
+        auto: true,
+    },
+    Layout {
+        name: "small",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 8),
+            ("range_check", 8),
+            ("ecdsa", 512),
+        ],
+        auto: true,
+    },
+    Layout {
+        name: "recursive",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 128),
+            ("range_check", 8),
+            ("bitwise", 8),
+        ],
+        auto: true,
+    },
+    Layout {
+        name: "recursive_with_poseidon",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 256),
+            ("range_check", 16),
+            ("bitwise", 16),
+            ("poseidon", 64),
+        ],
+        auto: true,
+    },
+    Layout {
+        name: "starknet",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 32),
+            ("range_check", 16),
+            ("ecdsa", 2048),
+            ("bitwise", 64),
+            ("ec_op", 1024),
+            ("poseidon", 32),
+        ],
+        auto: true,
+    },
+    Layout {
+        name: "starknet_with_keccak",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 32),
+            ("range_check", 16),
+            ("ecdsa", 2048),
+            ("bitwise", 64),
+            ("ec_op", 1024),
+            ("keccak", 2048),
+            ("poseidon", 32),
+        ],
+        auto: true,
+    },
+    Layout {
+        name: "all_cairo",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 256),
+            ("range_check", 8),
+            ("ecdsa", 2048),
+            ("bitwise", 16),
+            ("ec_op", 1024),
+            ("keccak", 2048),
+            ("poseidon", 256),
+            ("range_check96", 8),
+            ("add_mod", 128),
+            ("mul_mod", 256),
+        ],
+        auto: true,
+    },
+    // Variants of the layouts above that only differ in columns `auto` doesn't weigh
+    Layout {
+        name: "dex",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 8),
+            ("range_check", 8),
+            ("ecdsa", 512),
+        ],
+        auto: false,
+    },
+    Layout {
+        name: "recursive_large_output",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 128),
+            ("range_check", 8),
+            ("bitwise", 8),
+        ],
+        auto: false,
+    },
+    Layout {
+        name: "all_solidity",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 8),
+            ("range_check", 8),
+            ("ecdsa", 512),
+            ("bitwise", 256),
+            ("ec_op", 256),
+        ],
+        auto: false,
+    },
+    // The builtin ratios of the dynamic layout come with the run, they are unknown here
+    Layout {
+        name: "dynamic",
+        builtins: &[
+            ("output", 0),
+            ("pedersen", 0),
+            ("range_check", 0),
+            ("ecdsa", 0),
+            ("bitwise", 0),
+            ("ec_op", 0),
+            ("keccak", 0),
+            ("poseidon", 0),
+            ("range_check96", 0),
+            ("add_mod", 0),
+            ("mul_mod", 0),
+        ],
+        auto: false,
+    },
+];
+
+// Memory cells of one builtin instance, keccak instances come in components of 16
+fn cells_per_instance(builtin: &str) -> u64 {
+    match builtin {
+        "pedersen" => 3,
+        "ecdsa" => 2,
+        "bitwise" => 5,
+        "ec_op" | "add_mod" | "mul_mod" => 7,
+        "keccak" => 16 * 16,
+        "poseidon" => 6,
+        _ => 1,
+    }
+}
+
+// Sierra builtin types and the builtin they run on, the others are handled by the runner
+const SIERRA_BUILTINS: [(&str, &str); 8] = [
+    ("Pedersen", "pedersen"),
+    ("RangeCheck", "range_check"),
+    ("Bitwise", "bitwise"),
+    ("EcOp", "ec_op"),
+    ("Poseidon", "poseidon"),
+    ("RangeCheck96", "range_check96"),
+    ("AddMod", "add_mod"),
+    ("MulMod", "mul_mod"),
+];
+
+impl Layout {
+    fn supports(&self, builtins: &[String]) -> bool {
+        builtins
+            .iter()
+            .all(|builtin| self.builtins.iter().any(|(name, _)| name == builtin))
+    }
+
+    // Trace length in steps once padded for the builtin usage, without the program's own steps
+    fn builtin_trace_length(&self, usage: &BTreeMap<String, u64>) -> u64 {
+        self.builtins
+            .iter()
+            .filter_map(|(name, ratio)| {
+                let cells = usage.get(*name)?;
+                let instances = cells.div_ceil(cells_per_instance(name));
+                Some(instances * ratio)
+            })
+            .max()
+            .unwrap_or(0)
+            .next_power_of_two()
+    }
+}
+
+/// Builtins the program declares, by their layout name.
+pub fn declared_builtins(input: &CairoVersionedInput) -> Vec<String> {
+    match input {
+        CairoVersionedInput::Cairo0(input) => input.program.builtins.clone(),
+        CairoVersionedInput::Cairo(input) => sierra_builtins(&input.program.type_declarations),
+        CairoVersionedInput::Upload(input) => input.builtins.clone(),
+    }
+}
+
+/// Builtins a Sierra program with `type_declarations` uses, by their layout name.
+pub fn sierra_builtins(type_declarations: &Value) -> Vec<String> {
+    let generic_ids = type_declarations
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|declaration| declaration["long_id"]["generic_id"].as_str());
+    // cairo1-run writes the output to the output builtin in proof mode
+    let mut builtins = vec!["output".to_string()];
+    for generic_id in generic_ids {
+        let builtin = SIERRA_BUILTINS
+            .iter()
+            .find(|(sierra, _)| *sierra == generic_id)
+            .map(|(_, builtin)| builtin.to_string());
+        if let Some(builtin) = builtin.filter(|b| !builtins.contains(b)) {
+            builtins.push(builtin);
+        }
+    }
+    builtins
+}
+
+/// Whether `name` is `auto` or a layout the server can run and prove.
+pub fn is_known(name: &str) -> bool {
+    name == AUTO || LAYOUTS.iter().any(|layout| layout.name == name)
+}
+
+/// Builtins of `builtins` that the layout `name` doesn't provide, `None` for unknown layouts.
+pub fn missing_builtins(name: &str, builtins: &[String]) -> Option<Vec<String>> {
+    let layout = LAYOUTS.iter().find(|layout| layout.name == name)?;
+    let missing = builtins
+        .iter()
+        .filter(|builtin| !layout.supports(std::slice::from_ref(builtin)))
+        .cloned()
+        .collect();
+    Some(missing)
+}
+
+/// The smallest layout providing every builtin in `builtins`.
+pub fn smallest(builtins: &[String]) -> Result<&'static str, ProverError> {
+    LAYOUTS
+        .iter()
+        .find(|layout| layout.auto && layout.supports(builtins))
+        .map(|layout| layout.name)
+        .ok_or_else(|| {
+            ProverError::CustomError(format!(
+                "No supported layout provides the builtins {}",
+                builtins.join(", ")
+            ))
+        })
+}
+
+/// A layout providing `builtins` with a shorter trace than the execution in `public_input`.
+///
+/// Small layouts reserve few steps per builtin instance, so programs using many builtins are
+/// padded to long traces that a larger layout avoids. Traces are padded to a power of two, a
+/// trace longer than its builtins need is mostly the program's own steps, which are the same in
+/// every layout.
+pub(crate) fn shorter_trace(
+    builtins: &[String],
+    public_input: &PublicInput,
+) -> Option<&'static str> {
+    let usage = public_input.builtins();
+    let current = LAYOUTS
+        .iter()
+        .find(|layout| layout.name == public_input.layout)?;
+    if current.builtin_trace_length(&usage) < public_input.n_steps {
+        return None;
+    }
+    LAYOUTS
+        .iter()
+        .filter(|layout| layout.auto && layout.supports(builtins))
+        .map(|layout| (layout.builtin_trace_length(&usage), layout.name))
+        .filter(|(length, _)| *length < public_input.n_steps)
+        .min_by_key(|(length, _)| *length)
+        .map(|(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::job::tests::cairo_input;
+    use serde_json::json;
+
+    fn builtins(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_smallest_layout() {
+        assert_eq!(smallest(&[]).unwrap(), "plain");
+        assert_eq!(smallest(&builtins(&["output"])).unwrap(), "small");
+        assert_eq!(
+            smallest(&builtins(&["output", "pedersen", "range_check", "bitwise"])).unwrap(),
+            "recursive"
+        );
+        assert_eq!(
+            smallest(&builtins(&["output", "poseidon"])).unwrap(),
+            "recursive_with_poseidon"
+        );
+        assert_eq!(
+            smallest(&builtins(&["keccak"])).unwrap(),
+            "starknet_with_keccak"
+        );
+        assert!(smallest(&builtins(&["sha256"])).is_err());
+    }
+
+    #[test]
+    fn test_layouts_by_name() {
+        for name in ["dex", "recursive_large_output", "all_solidity", "dynamic"] {
+            assert!(is_known(name));
+        }
+        assert!(!is_known("recursiv"));
+        assert_eq!(
+            missing_builtins("all_solidity", &builtins(&["output", "ec_op", "poseidon"])),
+            Some(builtins(&["poseidon"]))
+        );
+        assert_eq!(
+            missing_builtins("dynamic", &builtins(&["keccak", "mul_mod"])),
+            Some(Vec::new())
+        );
+        // Only requested by name, `auto` keeps the layouts it knows the cost of
+        assert_eq!(
+            smallest(&builtins(&["output", "pedersen", "ecdsa", "ec_op"])).unwrap(),
+            "starknet"
+        );
+    }
+
+    #[test]
+    fn test_sierra_builtins() {
+        let CairoVersionedInput::Cairo(mut input) = cairo_input() else {
+            unreachable!()
+        };
+        input.program.type_declarations = json!([
+            {"long_id": {"generic_id": "felt252", "generic_args": []}},
+            {"long_id": {"generic_id": "Pedersen", "generic_args": []}},
+            {"long_id": {"generic_id": "RangeCheck", "generic_args": []}},
+            {"long_id": {"generic_id": "Pedersen", "generic_args": []}}
+        ]);
+        assert_eq!(
+            declared_builtins(&CairoVersionedInput::Cairo(input)),
+            builtins(&["output", "pedersen", "range_check"])
+        );
+    }
+
+    #[test]
+    fn test_shorter_trace() {
+        let public_input = |n_steps: u64, pedersen_cells: u64| -> PublicInput {
+            serde_json::from_value(json!({
+                "layout": "recursive",
+                "n_steps": n_steps,
+                "memory_segments": {
+                    "output": {"begin_addr": 0, "stop_ptr": 2},
+                    "pedersen": {"begin_addr": 2, "stop_ptr": 2 + pedersen_cells}
+                },
+                "public_memory": []
+            }))
+            .unwrap()
+        };
+        let declared = builtins(&["output", "pedersen", "range_check", "bitwise"]);
+        // 1000 hashes take 128000 steps in recursive, 32000 in starknet
+        assert_eq!(
+            shorter_trace(&declared, &public_input(131072, 3000)),
+            Some("starknet")
+        );
+        // The program's own steps dominate, no layout does better
+        assert_eq!(shorter_trace(&declared, &public_input(1024, 3)), None);
+    }
+}
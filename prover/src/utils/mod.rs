@@ -1,3 +1,11 @@
+pub mod cache;
 pub mod config;
+pub mod events;
 pub mod job;
+pub mod job_storage;
+pub mod layout;
+pub mod programs;
+pub mod public_input;
 pub mod shutdown;
+pub mod validation;
+pub mod workdir;
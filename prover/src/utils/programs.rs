@@ -0,0 +1,520 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use common::{
+    models::{CairoVersion, ProgramSummary},
+    prover_input::{CompiledProgram, ProgramSubmission, SubmissionParams},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+
+use super::job_storage::{FileJobStorage, JobStorage, JobStorageProvider};
+use crate::{auth::jwt::Claims, errors::ProverError, server::AppState};
+
+/// A registered program with every key that registered it, the body is stored on its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredProgram {
+    pub hash: String,
+    pub cairo_version: CairoVersion,
+    /// Size of the program's JSON in bytes
+    pub size: u64,
+    /// Registration time of each key as a unix timestamp in seconds, by hex encoded public key
+    pub owners: BTreeMap<String, u64>,
+}
+
+impl StoredProgram {
+    fn summary(&self, owner: Option<&str>) -> Option<ProgramSummary> {
+        let registered = match owner {
+            Some(owner) => *self.owners.get(owner)?,
+            None => self.owners.values().copied().min()?,
+        };
+        Some(ProgramSummary {
+            program_hash: self.hash.clone(),
+            cairo_version: self.cairo_version.clone(),
+            size: self.size,
+            registered,
+        })
+    }
+}
+
+/// How many programs a key may keep registered, and how many bytes they may take together.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramQuota {
+    pub max_programs: usize,
+    pub max_bytes: u64,
+}
+
+impl Default for ProgramQuota {
+    fn default() -> Self {
+        Self {
+            max_programs: 100,
+            max_bytes: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Programs {
+    entries: HashMap<String, StoredProgram>,
+    // Registrations writing the body of a program, which must not be removed under them
+    writing: HashMap<String, usize>,
+}
+
+impl Programs {
+    fn check_quota(&self, quota: &ProgramQuota, owner: &str, size: u64) -> Result<(), ProverError> {
+        let owned = self
+            .entries
+            .values()
+            .filter(|stored| stored.owners.contains_key(owner));
+        let (count, bytes) = owned.fold((0, 0), |(count, bytes), stored| {
+            (count + 1, bytes + stored.size)
+        });
+        if count >= quota.max_programs {
+            return Err(ProverError::QuotaExceeded(format!(
+                "at most {} programs may be registered per key",
+                quota.max_programs
+            )));
+        }
+        if bytes + size > quota.max_bytes {
+            return Err(ProverError::QuotaExceeded(format!(
+                "the programs of a key may take at most {} bytes",
+                quota.max_bytes
+            )));
+        }
+        Ok(())
+    }
+    fn finish_writing(&mut self, hash: &str) {
+        if let Some(count) = self.writing.get_mut(hash) {
+            *count -= 1;
+            if *count == 0 {
+                self.writing.remove(hash);
+            }
+        }
+    }
+}
+
+/// Programs uploaded once with `POST /programs` and proven by their hash afterwards.
+///
+/// Every key only sees the programs it registered, `None` as the owner stands for an admin key
+/// that sees all of them. A program is dropped once the last key that registered it deletes it.
+/// Only the summaries are kept in memory, the bodies are read from storage when proven, or
+/// from a scratch directory without persistent storage.
+#[derive(Clone)]
+pub struct ProgramRegistry {
+    programs: Arc<Mutex<Programs>>,
+    storage: JobStorage,
+    bodies: JobStorage,
+    // Removed once the last clone of the registry is dropped
+    _scratch: Option<Arc<TempDir>>,
+    quota: ProgramQuota,
+}
+
+impl ProgramRegistry {
+    /// Creates a registry backed by `storage`, reloading every program it already holds.
+    pub async fn new(storage: JobStorage, quota: ProgramQuota) -> Result<Self, ProverError> {
+        let entries = storage
+            .load_programs()
+            .await?
+            .into_iter()
+            .map(|program| (program.hash.clone(), program))
+            .collect();
+        let (bodies, scratch) = match &storage {
+            JobStorage::Persistent(_) => (storage.clone(), None),
+            JobStorage::Memory => {
+                let scratch = TempDir::new()?;
+                let bodies = FileJobStorage::new(scratch.path().to_path_buf()).await?;
+                (JobStorage::Persistent(bodies), Some(Arc::new(scratch)))
+            }
+        };
+        Ok(Self {
+            programs: Arc::new(Mutex::new(Programs {
+                entries,
+                writing: HashMap::new(),
+            })),
+            storage,
+            bodies,
+            _scratch: scratch,
+            quota,
+        })
+    }
+    /// Registers `program` for `owner`, returning its hash. Registering it again is a no-op.
+    ///
+    /// Fails with `QuotaExceeded` when `owner` has no room left for the program.
+    pub async fn register(
+        &self,
+        owner: &str,
+        program: CompiledProgram,
+    ) -> Result<String, ProverError> {
+        let contents = serde_json::to_vec(&program)?;
+        let stored = StoredProgram {
+            hash: prefix_hex::encode(Sha256::digest(&contents).to_vec()),
+            cairo_version: program.cairo_version(),
+            size: contents.len() as u64,
+            owners: BTreeMap::new(),
+        };
+        {
+            let mut programs = self.programs.lock().await;
+            if programs.entries.contains_key(&stored.hash) {
+                self.add_owner(&mut programs, stored.clone(), owner).await?;
+                return Ok(stored.hash);
+            }
+            programs.check_quota(&self.quota, owner, stored.size)?;
+            *programs.writing.entry(stored.hash.clone()).or_default() += 1;
+        }
+        // The body is the bulk of a registration, it is written without holding the lock
+        let written = self.bodies.save_program_body(&stored.hash, &contents).await;
+        let mut programs = self.programs.lock().await;
+        programs.finish_writing(&stored.hash);
+        let registered = match written {
+            Ok(()) => self.add_owner(&mut programs, stored.clone(), owner).await,
+            Err(e) => Err(e),
+        };
+        // The body of a failed registration goes, unless another one uses it
+        let unused = !programs.entries.contains_key(&stored.hash)
+            && !programs.writing.contains_key(&stored.hash);
+        if registered.is_err() && unused {
+            self.bodies.remove_program_body(&stored.hash).await?;
+        }
+        registered.map(|()| stored.hash)
+    }
+    // Records `owner` as registering `stored`, the small summary is written under the lock so
+    // that concurrent registrations are stored in order
+    async fn add_owner(
+        &self,
+        programs: &mut Programs,
+        stored: StoredProgram,
+        owner: &str,
+    ) -> Result<(), ProverError> {
+        let registered = programs
+            .entries
+            .get(&stored.hash)
+            .is_some_and(|stored| stored.owners.contains_key(owner));
+        if registered {
+            return Ok(());
+        }
+        programs.check_quota(&self.quota, owner, stored.size)?;
+        let stored = programs
+            .entries
+            .entry(stored.hash.clone())
+            .or_insert(stored);
+        stored
+            .owners
+            .insert(owner.to_string(), chrono::Utc::now().timestamp() as u64);
+        self.storage.save_program(stored).await
+    }
+    /// The program registered under `hash`, if `owner` registered it.
+    pub async fn get(
+        &self,
+        owner: Option<&str>,
+        hash: &str,
+    ) -> Result<Option<CompiledProgram>, ProverError> {
+        {
+            let programs = self.programs.lock().await;
+            let registered = programs.entries.get(hash).is_some_and(|stored| {
+                owner.map_or(true, |owner| stored.owners.contains_key(owner))
+            });
+            if !registered {
+                return Ok(None);
+            }
+        }
+        self.bodies.load_program_body(hash).await
+    }
+    /// Programs registered by `owner`, oldest registration first.
+    pub async fn list(&self, owner: Option<&str>) -> Vec<ProgramSummary> {
+        let programs = self.programs.lock().await;
+        let mut summaries: Vec<ProgramSummary> = programs
+            .entries
+            .values()
+            .filter_map(|stored| stored.summary(owner))
+            .collect();
+        summaries
+            .sort_by(|a, b| (a.registered, &a.program_hash).cmp(&(b.registered, &b.program_hash)));
+        summaries
+    }
+    /// Removes the registration of `owner`, or the program itself for an admin key.
+    ///
+    /// Returns false when `owner` never registered the program.
+    pub async fn remove(&self, owner: Option<&str>, hash: &str) -> Result<bool, ProverError> {
+        let mut programs = self.programs.lock().await;
+        let Some(stored) = programs.entries.get_mut(hash) else {
+            return Ok(false);
+        };
+        match owner {
+            Some(owner) => {
+                if stored.owners.remove(owner).is_none() {
+                    return Ok(false);
+                }
+            }
+            None => stored.owners.clear(),
+        }
+        if !stored.owners.is_empty() {
+            let stored = stored.clone();
+            self.storage.save_program(&stored).await?;
+            return Ok(true);
+        }
+        programs.entries.remove(hash);
+        self.storage.remove_program(hash).await?;
+        // A registration writing the same body again keeps it
+        if !programs.writing.contains_key(hash) {
+            self.bodies.remove_program_body(hash).await?;
+        }
+        Ok(true)
+    }
+    /// Splits a submission into its program, program input and parameters. The program is the
+    /// one the submission embeds, or the one `owner` registered with its `program_hash`.
+    pub async fn resolve<P, I>(
+        &self,
+        owner: Option<&str>,
+        submission: ProgramSubmission<P, I>,
+    ) -> Result<(P, I, SubmissionParams), ProverError>
+    where
+        P: TryFrom<CompiledProgram, Error = CompiledProgram>,
+    {
+        let (program, program_hash, program_input, params) = submission.into_parts();
+        let program = match (program, program_hash) {
+            (Some(program), None) => program,
+            (None, Some(hash)) => {
+                let program = self
+                    .get(owner, &hash)
+                    .await?
+                    .ok_or_else(|| ProverError::NotFound(format!("Program {hash}")))?;
+                P::try_from(program).map_err(|program| {
+                    ProverError::CustomError(format!(
+                        "Program {hash} is a {:?} program",
+                        program.cairo_version()
+                    ))
+                })?
+            }
+            (Some(_), Some(_)) => {
+                return Err(ProverError::CustomError(
+                    "Submit either a program or a program_hash, not both".to_string(),
+                ))
+            }
+            (None, None) => {
+                return Err(ProverError::CustomError(
+                    "Submit a program or a program_hash".to_string(),
+                ))
+            }
+        };
+        Ok((program, program_input, params))
+    }
+}
+
+/// The key whose programs `claims` may use, `None` for admin keys which may use all of them.
+pub fn program_owner<'a>(claims: &'a Claims, app_state: &AppState) -> Option<&'a str> {
+    (!claims.is_admin(&app_state.admin_keys)).then_some(claims.sub.as_str())
+}
+
+/// Registers a compiled program for the caller, responding with its `program_hash`.
+pub async fn register_program(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    Json(program): Json<CompiledProgram>,
+) -> Result<impl IntoResponse, ProverError> {
+    let program_hash = app_state.programs.register(&claims.sub, program).await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({ "program_hash": program_hash })),
+    ))
+}
+
+/// Lists the caller's programs, or every program when the caller holds an admin key.
+pub async fn list_programs(
+    State(app_state): State<AppState>,
+    claims: Claims,
+) -> Json<Vec<ProgramSummary>> {
+    let owner = program_owner(&claims, &app_state);
+    Json(app_state.programs.list(owner).await)
+}
+
+pub async fn delete_program(
+    Path(hash): Path<String>,
+    State(app_state): State<AppState>,
+    claims: Claims,
+) -> Result<(), ProverError> {
+    let owner = program_owner(&claims, &app_state);
+    if !app_state.programs.remove(owner, &hash).await? {
+        return Err(ProverError::NotFound(format!("Program {hash}")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::job_storage::FileJobStorage;
+    use common::prover_input::{Cairo0CompiledProgram, CairoCompiledProgram};
+    use serde_json::Value;
+    use starknet_types_core::felt::Felt;
+    use tempfile::tempdir;
+
+    const OWNER: &str = "0x01";
+    const OTHER: &str = "0x02";
+
+    fn program() -> CompiledProgram {
+        CompiledProgram::Cairo(CairoCompiledProgram {
+            type_declarations: Value::Null,
+            libfunc_declarations: Value::Null,
+            statements: Value::Array(Vec::new()),
+            funcs: Value::Null,
+        })
+    }
+
+    fn other_program() -> CompiledProgram {
+        CompiledProgram::Cairo(CairoCompiledProgram {
+            type_declarations: Value::Null,
+            libfunc_declarations: Value::Null,
+            statements: json!(["return"]),
+            funcs: Value::Null,
+        })
+    }
+
+    async fn memory_registry(quota: ProgramQuota) -> ProgramRegistry {
+        ProgramRegistry::new(JobStorage::Memory, quota)
+            .await
+            .unwrap()
+    }
+
+    fn submission(
+        program: Option<CairoCompiledProgram>,
+        hash: Option<&str>,
+    ) -> ProgramSubmission<CairoCompiledProgram, Vec<Felt>> {
+        let mut submission: ProgramSubmission<_, _> = serde_json::from_value(json!({
+            "program_hash": hash,
+            "program_input": ["0x1"],
+            "layout": "recursive",
+        }))
+        .unwrap();
+        submission.program = program;
+        submission
+    }
+
+    #[tokio::test]
+    async fn test_registry_ownership() {
+        let registry = memory_registry(ProgramQuota::default()).await;
+        let hash = registry.register(OWNER, program()).await.unwrap();
+        assert_eq!(registry.register(OTHER, program()).await.unwrap(), hash);
+        assert_eq!(registry.list(Some(OWNER)).await.len(), 1);
+        assert_eq!(registry.list(None).await.len(), 1);
+
+        // A program stays registered for the other key after one deletes it
+        assert!(registry.remove(Some(OWNER), &hash).await.unwrap());
+        assert!(!registry.remove(Some(OWNER), &hash).await.unwrap());
+        assert!(registry.get(Some(OWNER), &hash).await.unwrap().is_none());
+        assert!(registry.list(Some(OWNER)).await.is_empty());
+        assert_eq!(
+            registry.get(Some(OTHER), &hash).await.unwrap(),
+            Some(program())
+        );
+
+        assert!(registry.remove(Some(OTHER), &hash).await.unwrap());
+        assert!(registry.get(None, &hash).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_program_hash() {
+        let registry = memory_registry(ProgramQuota::default()).await;
+        let hash = registry.register(OWNER, program()).await.unwrap();
+        let CompiledProgram::Cairo(program) = program() else {
+            unreachable!()
+        };
+
+        let (resolved, program_input, params) = registry
+            .resolve(Some(OWNER), submission(None, Some(&hash)))
+            .await
+            .unwrap();
+        assert_eq!(resolved, program);
+        assert_eq!(program_input, vec![Felt::ONE]);
+        assert_eq!(params.layout, "recursive");
+
+        let result = registry
+            .resolve(Some(OTHER), submission(None, Some(&hash)))
+            .await;
+        assert!(matches!(result, Err(ProverError::NotFound(_))));
+        let cairo0: ProgramSubmission<Cairo0CompiledProgram, Vec<Felt>> =
+            serde_json::from_value(json!({
+                "program_hash": hash,
+                "program_input": [],
+                "layout": "recursive",
+            }))
+            .unwrap();
+        let result = registry.resolve(Some(OWNER), cairo0).await;
+        assert!(matches!(result, Err(ProverError::CustomError(_))));
+        let result = registry
+            .resolve(Some(OWNER), submission(Some(program), Some(&hash)))
+            .await;
+        assert!(matches!(result, Err(ProverError::CustomError(_))));
+        let result = registry.resolve(Some(OWNER), submission(None, None)).await;
+        assert!(matches!(result, Err(ProverError::CustomError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_registry_is_reloaded() {
+        let dir = tempdir().unwrap();
+        let storage = || async {
+            JobStorage::Persistent(FileJobStorage::new(dir.path().to_path_buf()).await.unwrap())
+        };
+        let registry = || async {
+            ProgramRegistry::new(storage().await, ProgramQuota::default())
+                .await
+                .unwrap()
+        };
+        let hash = registry().await.register(OWNER, program()).await.unwrap();
+        // The body is stored apart from the summary and read when the program is used
+        let body = dir.path().join("programs").join(format!("{hash}.program"));
+        assert!(body.exists());
+
+        let registry = registry().await;
+        assert_eq!(
+            registry.get(Some(OWNER), &hash).await.unwrap(),
+            Some(program())
+        );
+        assert!(registry.remove(None, &hash).await.unwrap());
+        assert!(!body.exists());
+
+        let registry = ProgramRegistry::new(storage().await, ProgramQuota::default())
+            .await
+            .unwrap();
+        assert!(registry.list(None).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_registry_quota() {
+        let registry = memory_registry(ProgramQuota {
+            max_programs: 1,
+            max_bytes: 1024,
+        })
+        .await;
+        let hash = registry.register(OWNER, program()).await.unwrap();
+        // Registering the same program again takes no room
+        assert_eq!(registry.register(OWNER, program()).await.unwrap(), hash);
+        let result = registry.register(OWNER, other_program()).await;
+        assert!(matches!(result, Err(ProverError::QuotaExceeded(_))));
+        assert_eq!(registry.list(Some(OWNER)).await.len(), 1);
+        // Other keys have their own quota
+        registry.register(OTHER, other_program()).await.unwrap();
+
+        // Deleting a program makes room again
+        assert!(registry.remove(Some(OWNER), &hash).await.unwrap());
+        registry.register(OWNER, other_program()).await.unwrap();
+
+        let registry = memory_registry(ProgramQuota {
+            max_programs: 10,
+            max_bytes: 16,
+        })
+        .await;
+        let result = registry.register(OWNER, program()).await;
+        assert!(matches!(result, Err(ProverError::QuotaExceeded(_))));
+        assert!(registry.list(None).await.is_empty());
+    }
+}
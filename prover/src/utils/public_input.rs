@@ -0,0 +1,114 @@
+use common::models::RunResult;
+use serde::Deserialize;
+use starknet_types_core::felt::Felt;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use crate::errors::ProverError;
+
+// Segments of every execution, the remaining ones belong to builtins
+const NON_BUILTIN_SEGMENTS: [&str; 2] = ["program", "execution"];
+
+/// The parts of the AIR public input written by the runner that describe the execution.
+#[derive(Deserialize)]
+pub(crate) struct PublicInput {
+    pub(crate) layout: String,
+    pub(crate) n_steps: u64,
+    memory_segments: HashMap<String, MemorySegment>,
+    public_memory: Vec<MemoryCell>,
+}
+
+#[derive(Deserialize)]
+struct MemorySegment {
+    begin_addr: u64,
+    stop_ptr: u64,
+}
+
+#[derive(Deserialize)]
+struct MemoryCell {
+    address: u64,
+    value: String,
+}
+
+impl PublicInput {
+    pub(crate) fn read_from_file(path: &Path) -> Result<Self, ProverError> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Public memory of the segment `name` in address order, empty if the program doesn't use it.
+    pub(crate) fn segment(&self, name: &str) -> Result<Vec<Felt>, ProverError> {
+        let Some(segment) = self.memory_segments.get(name) else {
+            return Ok(Vec::new());
+        };
+        let mut cells: Vec<&MemoryCell> = self
+            .public_memory
+            .iter()
+            .filter(|cell| (segment.begin_addr..segment.stop_ptr).contains(&cell.address))
+            .collect();
+        cells.sort_by_key(|cell| cell.address);
+        cells
+            .into_iter()
+            .map(|cell| {
+                Felt::from_hex(&cell.value).map_err(|e| ProverError::CustomError(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Number of memory cells used by each builtin.
+    pub(crate) fn builtins(&self) -> BTreeMap<String, u64> {
+        self.memory_segments
+            .iter()
+            .filter(|(name, _)| !NON_BUILTIN_SEGMENTS.contains(&name.as_str()))
+            .map(|(name, segment)| {
+                let used = segment.stop_ptr.saturating_sub(segment.begin_addr);
+                (name.clone(), used)
+            })
+            .collect()
+    }
+
+    pub(crate) fn run_result(self) -> Result<RunResult, ProverError> {
+        Ok(RunResult {
+            program_output: self.segment("output")?,
+            builtins: self.builtins(),
+            n_steps: self.n_steps,
+            layout: self.layout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_result() {
+        let public_input: PublicInput = serde_json::from_str(
+            r#"{
+                "layout": "recursive",
+                "n_steps": 16,
+                "memory_segments": {
+                    "program": {"begin_addr": 1, "stop_ptr": 3},
+                    "execution": {"begin_addr": 3, "stop_ptr": 10},
+                    "output": {"begin_addr": 10, "stop_ptr": 12},
+                    "range_check": {"begin_addr": 12, "stop_ptr": 15}
+                },
+                "public_memory": [
+                    {"address": 11, "value": "0x7", "page": 0},
+                    {"address": 1, "value": "0x40780017fff7fff", "page": 0},
+                    {"address": 10, "value": "0x2a", "page": 0}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let result = public_input.run_result().unwrap();
+        assert_eq!(result.program_output, vec![Felt::from(42), Felt::from(7)]);
+        assert_eq!(result.n_steps, 16);
+        assert_eq!(result.layout, "recursive");
+        assert_eq!(
+            result.builtins,
+            BTreeMap::from([("output".to_string(), 2), ("range_check".to_string(), 3)])
+        );
+    }
+}
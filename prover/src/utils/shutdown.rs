@@ -1,10 +1,9 @@
 use crate::threadpool::ThreadPool;
 use std::sync::Arc;
 use tokio::signal;
-use tokio::sync::Mutex;
 use tracing::info; // Import the logging macro
 
-pub async fn shutdown_signal(thread_pool: Arc<Mutex<ThreadPool>>) {
+pub async fn shutdown_signal(thread_pool: Arc<ThreadPool>) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -33,7 +32,6 @@ pub async fn shutdown_signal(thread_pool: Arc<Mutex<ThreadPool>>) {
 
     // Trigger thread pool shutdown
     info!("Shutting down the thread pool...");
-    let mut thread_pool = thread_pool.lock().await;
     if let Err(e) = thread_pool.shutdown().await {
         eprintln!("Error during thread pool shutdown: {:?}", e);
     } else {
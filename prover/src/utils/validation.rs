@@ -0,0 +1,240 @@
+use common::models::FieldError;
+use std::ops::RangeInclusive;
+
+use super::{config::Template, layout};
+use crate::{errors::ProverError, threadpool::CairoVersionedInput};
+
+const N_QUERIES: RangeInclusive<u32> = 1..=128;
+const POW_BITS: RangeInclusive<u32> = 0..=50;
+const LOG_N_COSETS: RangeInclusive<u32> = 1..=6;
+const LAST_LAYER_DEGREE_BOUND: RangeInclusive<u32> = 1..=4096;
+const CONSTRAINT_POLYNOMIAL_TASK_SIZE: RangeInclusive<u32> = 1..=4096;
+const N_OUT_OF_MEMORY_MERKLE_LAYERS: RangeInclusive<u32> = 0..=16;
+
+/// Checks a submission before it is queued, collecting every invalid field.
+///
+/// `min_security_bits` is the operator's lower bound on the security of the requested proof.
+pub fn validate(
+    input: &CairoVersionedInput,
+    min_security_bits: Option<u32>,
+) -> Result<(), ProverError> {
+    let mut errors = Vec::new();
+    let mut reject = |field: &str, message: String| {
+        errors.push(FieldError {
+            field: field.to_string(),
+            message,
+        })
+    };
+
+    let name = input.layout();
+    let builtins = layout::declared_builtins(input);
+    if !layout::is_known(name) {
+        reject("layout", format!("Unknown layout {name}"));
+    } else if name == layout::AUTO {
+        if let Err(e) = layout::smallest(&builtins) {
+            reject("layout", e.to_string());
+        }
+    } else if let Some(missing) = layout::missing_builtins(name, &builtins) {
+        if !missing.is_empty() {
+            reject(
+                "layout",
+                format!(
+                    "Layout {name} doesn't provide the builtins {}",
+                    missing.join(", ")
+                ),
+            );
+        }
+    }
+
+    let n_queries = input.n_queries();
+    let pow_bits = input.pow_bits();
+    let in_range = |value: Option<u32>, range: &RangeInclusive<u32>| {
+        value.map_or(true, |value| range.contains(&value))
+    };
+    if !in_range(n_queries, &N_QUERIES) {
+        reject(
+            "n_queries",
+            format!(
+                "Must be between {} and {}",
+                N_QUERIES.start(),
+                N_QUERIES.end()
+            ),
+        );
+    }
+    if !in_range(pow_bits, &POW_BITS) {
+        reject(
+            "pow_bits",
+            format!(
+                "Must be between {} and {}",
+                POW_BITS.start(),
+                POW_BITS.end()
+            ),
+        );
+    }
+    let params = input.prover_params().cloned().unwrap_or_default();
+    if !in_range(params.log_n_cosets, &LOG_N_COSETS) {
+        reject(
+            "prover_params.log_n_cosets",
+            format!(
+                "Must be between {} and {}",
+                LOG_N_COSETS.start(),
+                LOG_N_COSETS.end()
+            ),
+        );
+    }
+    let bound_is_valid =
+        |bound: u32| bound.is_power_of_two() && LAST_LAYER_DEGREE_BOUND.contains(&bound);
+    if !params.last_layer_degree_bound.map_or(true, bound_is_valid) {
+        reject(
+            "prover_params.last_layer_degree_bound",
+            format!(
+                "Must be a power of two up to {}",
+                LAST_LAYER_DEGREE_BOUND.end()
+            ),
+        );
+    }
+    let config = input.prover_config().cloned().unwrap_or_default();
+    if !in_range(
+        config.constraint_polynomial_task_size,
+        &CONSTRAINT_POLYNOMIAL_TASK_SIZE,
+    ) {
+        reject(
+            "prover_config.constraint_polynomial_task_size",
+            format!(
+                "Must be between {} and {}",
+                CONSTRAINT_POLYNOMIAL_TASK_SIZE.start(),
+                CONSTRAINT_POLYNOMIAL_TASK_SIZE.end()
+            ),
+        );
+    }
+    if !in_range(
+        config.n_out_of_memory_merkle_layers,
+        &N_OUT_OF_MEMORY_MERKLE_LAYERS,
+    ) {
+        reject(
+            "prover_config.n_out_of_memory_merkle_layers",
+            format!(
+                "Must be between {} and {}",
+                N_OUT_OF_MEMORY_MERKLE_LAYERS.start(),
+                N_OUT_OF_MEMORY_MERKLE_LAYERS.end()
+            ),
+        );
+    }
+    let security = Template::new(n_queries, pow_bits, Some(&params)).security_bits();
+    // Each of these raises the security, so each is reported
+    if let Some(min) = min_security_bits.filter(|min| security < *min) {
+        for field in ["n_queries", "pow_bits", "prover_params.log_n_cosets"] {
+            reject(
+                field,
+                format!("The proof would have {security} bits of security, the minimum is {min}"),
+            );
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ProverError::InvalidInput(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::job::tests::cairo_input;
+    use common::prover_input::{ProverConfig, ProverParams};
+
+    fn input(layout: &str, n_queries: Option<u32>, pow_bits: Option<u32>) -> CairoVersionedInput {
+        let CairoVersionedInput::Cairo(mut input) = cairo_input() else {
+            unreachable!()
+        };
+        input.layout = layout.to_string();
+        input.n_queries = n_queries;
+        input.pow_bits = pow_bits;
+        CairoVersionedInput::Cairo(input)
+    }
+
+    fn fields(result: Result<(), ProverError>) -> Vec<String> {
+        match result {
+            Err(ProverError::InvalidInput(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            other => panic!("expected field errors, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_valid_submission() {
+        validate(&input("recursive", Some(16), Some(20)), Some(60)).unwrap();
+        validate(&input("auto", None, None), None).unwrap();
+    }
+
+    #[test]
+    fn test_prover_params() {
+        let mut submission = input("recursive", Some(16), Some(20));
+        let CairoVersionedInput::Cairo(cairo) = &mut submission else {
+            unreachable!()
+        };
+        cairo.prover_params = Some(ProverParams {
+            log_n_cosets: Some(9),
+            last_layer_degree_bound: Some(100),
+            ..Default::default()
+        });
+        assert_eq!(
+            fields(validate(&submission, None)),
+            [
+                "prover_params.log_n_cosets",
+                "prover_params.last_layer_degree_bound"
+            ]
+        );
+
+        // A larger blowup makes up for fewer queries
+        let CairoVersionedInput::Cairo(cairo) = &mut submission else {
+            unreachable!()
+        };
+        cairo.prover_params = Some(ProverParams {
+            log_n_cosets: Some(4),
+            ..Default::default()
+        });
+        validate(&submission, Some(80)).unwrap();
+    }
+
+    #[test]
+    fn test_prover_config() {
+        let mut submission = input("recursive", None, None);
+        let CairoVersionedInput::Cairo(cairo) = &mut submission else {
+            unreachable!()
+        };
+        cairo.prover_config = Some(ProverConfig {
+            constraint_polynomial_task_size: Some(0),
+            n_out_of_memory_merkle_layers: Some(17),
+            cached_lde_config: None,
+        });
+        assert_eq!(
+            fields(validate(&submission, None)),
+            [
+                "prover_config.constraint_polynomial_task_size",
+                "prover_config.n_out_of_memory_merkle_layers"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_field_errors() {
+        assert_eq!(
+            fields(validate(&input("recursiv", Some(0), Some(70)), None)),
+            ["layout", "n_queries", "pow_bits"]
+        );
+        // The Sierra program declares nothing, but the output builtin is always used
+        assert_eq!(
+            fields(validate(&input("plain", None, None), None)),
+            ["layout"]
+        );
+        // 16 queries with a blowup of 8 and 20 bits of work are 68 bits
+        assert_eq!(
+            fields(validate(&input("recursive", Some(16), Some(20)), Some(80))),
+            ["n_queries", "pow_bits", "prover_params.log_n_cosets"]
+        );
+        // Layouts `auto` never picks are still accepted by name
+        validate(&input("recursive_large_output", None, None), None).unwrap();
+        validate(&input("dynamic", None, None), None).unwrap();
+    }
+}
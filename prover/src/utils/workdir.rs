@@ -0,0 +1,170 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+};
+use common::models::{JobId, JobStatus};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio_util::io::ReaderStream;
+
+use super::job::JobStore;
+use crate::{
+    auth::jwt::Claims, errors::ProverError, server::AppState, threadpool::prove::ProvePaths,
+};
+
+// How often the janitor looks for expired jobs and work directories to delete
+const JANITOR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What happens to the work directory of a job once it finished, selected in `Args`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RetentionPolicy {
+    /// Delete the work directory as soon as the job finished
+    Delete,
+    /// Keep the work directory of failed jobs for the retention period
+    #[default]
+    KeepOnFailure,
+    /// Keep every work directory for the retention period
+    Keep,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    pub policy: RetentionPolicy,
+    /// Time after the job finished its work directory is kept for, unless deleted right away
+    pub keep_for: Duration,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            policy: RetentionPolicy::default(),
+            keep_for: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl Retention {
+    /// Whether the work directory of a finished job with `status` and `age` goes.
+    pub fn expired(&self, status: &JobStatus, age: Duration) -> bool {
+        match self.policy {
+            RetentionPolicy::Delete => true,
+            RetentionPolicy::KeepOnFailure => {
+                !matches!(status, JobStatus::Failed) || age >= self.keep_for
+            }
+            RetentionPolicy::Keep => age >= self.keep_for,
+        }
+    }
+}
+
+/// Forgets expired jobs and deletes the work directories of finished jobs past their retention,
+/// until the server stops.
+pub async fn janitor(job_store: JobStore) {
+    let mut interval = tokio::time::interval(JANITOR_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = job_store.clear_old_jobs().await {
+            tracing::warn!("Failed to clear old jobs: {}", e);
+        }
+        if let Err(e) = job_store.clean_workdirs().await {
+            tracing::warn!("Failed to clean work directories: {}", e);
+        }
+    }
+}
+
+/// Deletes a work directory, which may already be gone.
+pub(crate) async fn remove(dir: &std::path::Path) -> Result<(), ProverError> {
+    match tokio::fs::remove_dir_all(dir).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Files of a job's work directory that can be downloaded.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Artifact {
+    PublicInput,
+    PrivateInput,
+    Trace,
+    Memory,
+    Params,
+}
+
+impl Artifact {
+    fn path(self, paths: &ProvePaths) -> &std::path::Path {
+        match self {
+            Artifact::PublicInput => &paths.public_input_file,
+            Artifact::PrivateInput => &paths.private_input_file,
+            Artifact::Trace => &paths.trace_file,
+            Artifact::Memory => &paths.memory_file,
+            Artifact::Params => &paths.params_file,
+        }
+    }
+    fn content_type(self) -> &'static str {
+        match self {
+            Artifact::Trace | Artifact::Memory => "application/octet-stream",
+            _ => "application/json",
+        }
+    }
+}
+
+/// Streams a file the runner or the prover wrote for the job, as long as it is retained.
+pub async fn get_artifact(
+    Path((id, artifact)): Path<(JobId, Artifact)>,
+    State(app_state): State<AppState>,
+    claims: Claims,
+) -> Result<impl IntoResponse, ProverError> {
+    let Some(job) = app_state.job_store.get_job(&id).await else {
+        return Err(ProverError::CustomError("Job not found".to_string()));
+    };
+    job.check_access(&claims, &app_state.admin_keys)?;
+    let not_found = || ProverError::NotFound(format!("Artifacts of job {id}"));
+    let workdir = job.workdir.ok_or_else(not_found)?;
+    let paths = ProvePaths::in_dir(&workdir);
+    let path = artifact.path(&paths);
+    let file = match tokio::fs::File::open(path).await {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(not_found()),
+        file => file?,
+    };
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    Ok((
+        [
+            (header::CONTENT_TYPE, artifact.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention() {
+        let hour = Duration::from_secs(60 * 60);
+        let retention = |policy| Retention {
+            policy,
+            keep_for: hour,
+        };
+        let failed = JobStatus::Failed;
+        let completed = JobStatus::Completed;
+
+        assert!(retention(RetentionPolicy::Delete).expired(&failed, Duration::ZERO));
+        let keep_on_failure = retention(RetentionPolicy::KeepOnFailure);
+        assert!(keep_on_failure.expired(&completed, Duration::ZERO));
+        assert!(!keep_on_failure.expired(&failed, Duration::ZERO));
+        assert!(keep_on_failure.expired(&failed, hour));
+        let keep = retention(RetentionPolicy::Keep);
+        assert!(!keep.expired(&completed, Duration::ZERO));
+        assert!(keep.expired(&completed, hour));
+    }
+}
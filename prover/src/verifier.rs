@@ -1,9 +1,10 @@
-use crate::{auth::jwt::Claims, extractors::workdir::TempDirHandle};
-use axum::Json;
-
-use std::process::Command;
+use crate::{
+    auth::jwt::Claims, backend::ProverBackend, extractors::workdir::TempDirHandle, server::AppState,
+};
+use axum::{extract::State, Json};
 
 pub async fn verify_proof(
+    State(app_state): State<AppState>,
     TempDirHandle(dir): TempDirHandle,
     _claims: Claims,
     Json(proof): Json<String>,
@@ -17,21 +18,15 @@ pub async fn verify_proof(
         return Json(false);
     }
 
-    // Create the command to run the verifier
-    let mut command = Command::new("cpu_air_verifier");
-    command.arg("--in_file").arg(&file);
-
-    // Execute the command and capture the status
-    let status = command.status();
+    let verified = app_state.backend.verify(&file).await;
 
     // Remove the proof file
     if let Err(e) = std::fs::remove_file(&file) {
         eprintln!("Failed to remove proof file: {}", e);
     }
 
-    // Check if the command was successful
-    match status {
-        Ok(exit_status) => Json(exit_status.success()),
+    match verified {
+        Ok(verified) => Json(verified),
         Err(e) => {
             eprintln!("Failed to execute verifier: {}", e);
             Json(false)
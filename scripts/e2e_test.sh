@@ -50,7 +50,8 @@ $CONTAINER_ENGINE run -d --name http_prover_test $REPLACE_FLAG \
     --message-expiration-time 3600 \
     --session-expiration-time 3600 \
     --authorized-keys $PUBLIC_KEY,$ADMIN_PUBLIC_KEY1,$ADMIN_PUBLIC_KEY2 \
-    --admin-keys $ADMIN_PUBLIC_KEY1,$ADMIN_PUBLIC_KEY2
+    --admin-keys $ADMIN_PUBLIC_KEY1,$ADMIN_PUBLIC_KEY2 \
+    --backend "${PROVER_BACKEND:-stone}"
 
 start_time=$(date +%s)
 